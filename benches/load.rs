@@ -0,0 +1,57 @@
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use hyper::service::{make_service_fn, service_fn};
+use hyper::Server;
+
+use std::convert::Infallible;
+use std::process::{Command, Stdio};
+
+// The benchmark only needs the server helpers of the integration tests.
+#[allow(dead_code)]
+#[path = "../tests/common/mod.rs"]
+mod common;
+
+// Starts an HTTP/1.1 server on a random local port and returns its URL.
+// It does as little as possible, so the time measured is mostly burst's own.
+fn server() -> String {
+    let addr = common::spawn_server(|listener| async move {
+        let make_svc =
+            make_service_fn(|_conn| async { Ok::<_, Infallible>(service_fn(common::hello)) });
+        Server::from_tcp(listener)
+            .unwrap()
+            .serve(make_svc)
+            .await
+            .unwrap();
+    });
+
+    format!("http://{}", addr)
+}
+
+fn burst(host: &str, load: u64, workers: usize) {
+    let status = Command::new(env!("CARGO_BIN_EXE_burst"))
+        .args(["--host", host])
+        .args(["-l", &load.to_string()])
+        .args(["-w", &workers.to_string()])
+        .stdout(Stdio::null())
+        .status()
+        .unwrap();
+    assert!(status.success());
+}
+
+// Measures how long burst takes to send a burst of requests against a local
+// server, to catch per-request overhead in the load generator itself.
+fn load(c: &mut Criterion) {
+    let host = server();
+
+    let mut group = c.benchmark_group("load");
+    group.sample_size(10);
+    for load in [100, 1_000, 10_000] {
+        group.throughput(Throughput::Elements(load));
+        group.bench_with_input(BenchmarkId::from_parameter(load), &load, |b, &load| {
+            b.iter(|| burst(&host, load, 50))
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, load);
+criterion_main!(benches);
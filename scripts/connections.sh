@@ -0,0 +1,33 @@
+#!/usr/sbin/dtrace -s
+
+#pragma D option quiet
+
+burst*:::batch-tick
+{
+	printf("batch %d started %d ms into the run\n", arg1, arg2 / 1000000);
+}
+
+burst*:::connection-open
+{
+	@connections[copyinstr(arg1)] = count();
+}
+
+burst*:::request-redirect
+{
+	@redirects[copyinstr(arg2)] = count();
+}
+
+burst*:::request-error
+{
+	@errors[copyinstr(arg3), copyinstr(arg2)] = count();
+}
+
+dtrace:::END
+{
+	printf("Connections opened per URL:\n");
+	printa("  %-60s %@d\n", @connections);
+	printf("Redirects followed per URL:\n");
+	printa("  %-60s %@d\n", @redirects);
+	printf("Errors per URL and class:\n");
+	printa("  %-60s %-10s %@d\n", @errors);
+}
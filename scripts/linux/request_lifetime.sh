@@ -0,0 +1,31 @@
+#!/bin/sh
+#
+# Summarises the lifetime of the requests burst sends, whatever their method,
+# in nanoseconds. Pass the path to the burst binary if it isn't on the PATH,
+# and a number of seconds to stop after that long instead of on Ctrl-C:
+#
+#   sudo ./scripts/linux/request_lifetime.sh ./target/release/burst 10
+
+burst=${1:-$(command -v burst)}
+seconds=${2:-0}
+
+stop=""
+if [ "$seconds" -gt 0 ]; then
+	stop="interval:s:$seconds { exit(); }"
+fi
+
+exec bpftrace -e "
+usdt:$burst:burst:get__done,
+usdt:$burst:burst:post__done,
+usdt:$burst:burst:put__done,
+usdt:$burst:burst:patch__done
+{
+	@total_requests = count();
+	@average_request_lifetime = avg(arg2);
+	@max_request_lifetime = max(arg2);
+	@min_request_lifetime = min(arg2);
+	@request_lifetimes = hist(arg2);
+}
+
+$stop
+"
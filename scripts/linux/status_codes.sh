@@ -0,0 +1,32 @@
+#!/bin/sh
+#
+# Breaks the responses burst gets down per status code, with a histogram of
+# their latencies in nanoseconds, and counts errors per class. Pass the path
+# to the burst binary if it isn't on the PATH, and a number of seconds to
+# stop after that long instead of on Ctrl-C:
+#
+#   sudo ./scripts/linux/status_codes.sh ./target/release/burst 10
+
+burst=${1:-$(command -v burst)}
+seconds=${2:-0}
+
+stop=""
+if [ "$seconds" -gt 0 ]; then
+	stop="interval:s:$seconds { exit(); }"
+fi
+
+# Strings are passed as a pointer followed by their length.
+exec bpftrace -e "
+usdt:$burst:burst:response__status
+{
+	@responses[arg1] = count();
+	@latencies[arg1] = hist(arg2);
+}
+
+usdt:$burst:burst:request__error
+{
+	@errors[str(arg2, arg3)] = count();
+}
+
+$stop
+"
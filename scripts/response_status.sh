@@ -0,0 +1,24 @@
+#!/usr/sbin/dtrace -s
+
+#pragma D option quiet
+
+burst*:::response-status
+{
+	@responses[arg1] = count();
+	@latency[arg1] = quantize(arg2);
+}
+
+burst*:::request-error
+{
+	@errors[copyinstr(arg2)] = count();
+}
+
+dtrace:::END
+{
+	printf("Responses per status code:\n");
+	printa("  %-10d %@d\n", @responses);
+	printf("Errors per class:\n");
+	printa("  %-10s %@d\n", @errors);
+	printf("Response latencies per status code in nanoseconds:\n");
+	printa(@latency);
+}
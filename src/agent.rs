@@ -0,0 +1,222 @@
+use hyper::header::{AUTHORIZATION, CONTENT_TYPE};
+use hyper::service::{make_service_fn, service_fn};
+use hyper::{Body, Method, Request, Response, Server, StatusCode};
+use serde::{Deserialize, Serialize};
+
+use std::any::Any;
+use std::convert::Infallible;
+use std::mem;
+use std::net::SocketAddr;
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::{Arc, Mutex};
+
+use crate::app;
+use crate::client::Client;
+use crate::secret::Secret;
+
+/// Work a coordinator hands to an agent: the flags of a regular run, with
+/// the agent's share of the load.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Job {
+    pub args: Vec<String>,
+}
+
+// What the agent is doing with the job it was last given.
+#[derive(Default)]
+enum State {
+    #[default]
+    Idle,
+    Prepared(Arc<Client>),
+    Running,
+    /// The report for the agent's share of the run as JSON, or why the run
+    /// failed.
+    Done(Result<Vec<u8>, String>),
+}
+
+type Shared = Arc<Mutex<State>>;
+
+/// Waits for a coordinator to hand it load to send.
+///
+/// A coordinator first POSTs a `Job` to `/prepare` on every agent, and once
+/// they're all ready POSTs to `/start`, so the agents start together. The
+/// run goes on in the background, and the coordinator GETs `/report` until
+/// the report for the agent's share of the run is ready. Every request has
+/// to carry `token` as a bearer token.
+pub async fn listen(addr: SocketAddr, token: Secret) {
+    let state: Shared = Arc::new(Mutex::new(State::Idle));
+    let token = Arc::new(token);
+    let make_svc = make_service_fn(move |_conn| {
+        let state = state.clone();
+        let token = token.clone();
+        async move {
+            Ok::<_, Infallible>(service_fn(move |req| {
+                handle(req, state.clone(), token.clone())
+            }))
+        }
+    });
+
+    let server = Server::try_bind(&addr)
+        .unwrap_or_else(|e| panic!("Unable to listen on {}: {}", addr, e))
+        .serve(make_svc);
+    println!("Listening on {}", server.local_addr());
+
+    if let Err(e) = server.await {
+        eprintln!("Agent error: {}", e);
+    }
+}
+
+async fn handle(
+    req: Request<Body>,
+    state: Shared,
+    token: Arc<Secret>,
+) -> Result<Response<Body>, Infallible> {
+    if !authorized(&req, &token) {
+        return Ok(respond(
+            StatusCode::UNAUTHORIZED,
+            String::from("Missing or wrong token"),
+        ));
+    }
+
+    let res = match (req.method(), req.uri().path()) {
+        (&Method::POST, "/prepare") => prepare(req, &state).await,
+        (&Method::POST, "/start") => start(&state),
+        (&Method::GET, "/report") => report(&state),
+        _ => respond(StatusCode::NOT_FOUND, String::from("Not found")),
+    };
+    Ok(res)
+}
+
+// The token is compared in constant time, so it can't be guessed a byte at
+// a time from how long the agent takes to answer.
+fn authorized(req: &Request<Body>, token: &Secret) -> bool {
+    let expected = format!("Bearer {}", token.expose());
+    let given = req
+        .headers()
+        .get(AUTHORIZATION)
+        .map(|value| value.as_bytes())
+        .unwrap_or_default();
+    given.len() == expected.len()
+        && given
+            .iter()
+            .zip(expected.as_bytes())
+            .fold(0, |diff, (a, b)| diff | (a ^ b))
+            == 0
+}
+
+async fn prepare(req: Request<Body>, state: &Shared) -> Response<Body> {
+    if matches!(*state.lock().unwrap(), State::Running) {
+        return respond(
+            StatusCode::CONFLICT,
+            String::from("A job is already running"),
+        );
+    }
+
+    let body = match hyper::body::to_bytes(req.into_body()).await {
+        Ok(body) => body,
+        Err(e) => return respond(StatusCode::BAD_REQUEST, e.to_string()),
+    };
+    let job: Job = match serde_json::from_slice(&body) {
+        Ok(job) => job,
+        Err(e) => return respond(StatusCode::BAD_REQUEST, e.to_string()),
+    };
+    let matches = match app::matches_from(&job.args) {
+        Ok(matches) => matches,
+        Err(e) => return respond(StatusCode::BAD_REQUEST, e.message),
+    };
+    if let Err(e) = app::check_job(&matches).and_then(|_| app::check_run(&matches)) {
+        return respond(StatusCode::BAD_REQUEST, e);
+    }
+
+    // Building the client panics on anything check_run missed. That
+    // shouldn't take the agent down with it.
+    let client = match panic::catch_unwind(AssertUnwindSafe(|| app::burst_app(&matches))) {
+        Ok(client) => client,
+        Err(e) => {
+            let message = panic_message(e).unwrap_or_else(|| String::from("Invalid job"));
+            return respond(StatusCode::BAD_REQUEST, message);
+        }
+    };
+
+    let mut state = state.lock().unwrap();
+    if matches!(*state, State::Running) {
+        return respond(
+            StatusCode::CONFLICT,
+            String::from("A job is already running"),
+        );
+    }
+    *state = State::Prepared(Arc::new(client));
+    respond(StatusCode::OK, String::from("Ready"))
+}
+
+fn start(state: &Shared) -> Response<Body> {
+    let client = {
+        let mut state = state.lock().unwrap();
+        match mem::take(&mut *state) {
+            State::Prepared(client) => {
+                *state = State::Running;
+                client
+            }
+            other => {
+                *state = other;
+                return respond(
+                    StatusCode::CONFLICT,
+                    String::from("No job has been prepared"),
+                );
+            }
+        }
+    };
+
+    let run = tokio::spawn(async move {
+        match client.run().await {
+            Ok(report) => serde_json::to_vec(&report).map_err(|e| e.to_string()),
+            Err(e) => Err(format!("Unable to authenticate: {}", e)),
+        }
+    });
+    // A run that panics still has to end, or the coordinator would wait for
+    // its report forever.
+    let state = state.clone();
+    tokio::spawn(async move {
+        let result = match run.await {
+            Ok(result) => result,
+            Err(e) => match e.try_into_panic().map(panic_message) {
+                Ok(Some(message)) => Err(format!("The run panicked: {}", message)),
+                Ok(None) => Err(String::from("The run panicked")),
+                Err(e) => Err(format!("The run failed: {}", e)),
+            },
+        };
+        *state.lock().unwrap() = State::Done(result);
+    });
+
+    respond(StatusCode::ACCEPTED, String::from("Started"))
+}
+
+fn report(state: &Shared) -> Response<Body> {
+    match &*state.lock().unwrap() {
+        State::Running => respond(StatusCode::ACCEPTED, String::from("Running")),
+        State::Done(Ok(report)) => {
+            let mut res = Response::new(Body::from(report.clone()));
+            res.headers_mut()
+                .insert(CONTENT_TYPE, "application/json".parse().unwrap());
+            res
+        }
+        State::Done(Err(e)) => respond(StatusCode::BAD_GATEWAY, e.clone()),
+        State::Idle | State::Prepared(_) => respond(
+            StatusCode::CONFLICT,
+            String::from("No job has been started"),
+        ),
+    }
+}
+
+// The message a panic was raised with, if it was raised with one.
+fn panic_message(payload: Box<dyn Any + Send>) -> Option<String> {
+    payload
+        .downcast_ref::<String>()
+        .cloned()
+        .or_else(|| payload.downcast_ref::<&str>().map(|e| e.to_string()))
+}
+
+fn respond(status: StatusCode, message: String) -> Response<Body> {
+    let mut res = Response::new(Body::from(message));
+    *res.status_mut() = status;
+    res
+}
@@ -1,42 +1,157 @@
-use clap::{App, Arg};
-use reqwest::Method;
+use clap::{App, AppSettings, Arg, ArgGroup, ArgMatches, SubCommand};
+use reqwest::header::HeaderName;
+use reqwest::{Method, Url};
+use tokio::runtime::{self, Runtime};
 
+use std::env;
 use std::fs;
+use std::iter;
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::time::Duration;
 
+use crate::auth::{Auth, OAuth2};
+use crate::body::{self, Body};
 use crate::client::Client;
+use crate::compare::Comparison;
+use crate::compression::Encoding;
+use crate::config;
+use crate::coordinator::Coordinator;
+use crate::histogram::Scale;
+use crate::http::{self, HttpOptions, HttpVersion};
+use crate::proxy::ProxyOptions;
+use crate::report::{Outputs, Report};
+use crate::sampler::Sampler;
+use crate::secret::Secret;
+use crate::targets::{self, Strategy, Targets};
+use crate::tls::{self, CertType, TlsOptions};
+use crate::trace::{self, Tracer};
 
 macro_rules! validate_flag_error {
     ($flag:tt) => {
-        &format!("A value for {} is required", $flag)
+        || panic!("A value for {} is required", $flag)
     };
 }
 
+const RUN_CMD: &str = "run";
+const REPORT_CMD: &str = "report";
+const VALIDATE_CMD: &str = "validate";
+const COMPARE_CMD: &str = "compare";
+const BASELINE_ARG: &str = "baseline";
+const CANDIDATE_ARG: &str = "candidate";
+const TOLERANCE_FLAG: &str = "tolerance";
+const ALPHA_FLAG: &str = "alpha";
+const FILE_ARG: &str = "file";
+const AGENT_CMD: &str = "agent";
+const LISTEN_FLAG: &str = "listen";
+const COORDINATOR_CMD: &str = "coordinator";
+const AGENT_FLAG: &str = "agent";
+const RUN_ARGS: &str = "run";
+const TOKEN_FLAG: &str = "token";
+const TOKEN_ENV_FLAG: &str = "token-env";
+const TOKEN_FILE_FLAG: &str = "token-file";
+
+const CONFIG_FLAG: &str = "config";
+const OUTPUT_FLAG: &str = "output";
+const HTML_REPORT_FLAG: &str = "html-report";
+const HISTOGRAM_FLAG: &str = "histogram";
+const HISTOGRAM_STEP_FLAG: &str = "histogram-step";
 const LOAD_FLAG: &str = "load";
 const WORKERS_FLAG: &str = "workers";
+const THREADS_FLAG: &str = "threads";
+const SINGLE_THREADED_FLAG: &str = "single-threaded";
 const DURATION_FLAG: &str = "duration";
 const INTERVAL_FLAG: &str = "interval";
 const TIMEOUT_FLAG: &str = "timeout";
 const METHOD_FLAG: &str = "method";
 const BODY_FLAG: &str = "body";
 const BODY_FILE_FLAG: &str = "body-file";
+const BODY_ENCODING_FLAG: &str = "body-encoding";
+const STREAM_BODY_FLAG: &str = "stream-body";
+const FORM_FLAG: &str = "form";
+const DATA_URLENCODE_FLAG: &str = "data-urlencode";
+const COMPRESSED_FLAG: &str = "compressed";
 const HOST_FLAG: &str = "host";
+const HOSTS_FILE_FLAG: &str = "hosts-file";
+const STRATEGY_FLAG: &str = "strategy";
 const USER_FLAG: &str = "user";
 const PASS_FLAG: &str = "pass";
+const PASS_ENV_FLAG: &str = "pass-env";
+const PASS_FILE_FLAG: &str = "pass-file";
 const EXACT_FLAG: &str = "exact";
 const VERBOSE_FLAG: &str = "verbose";
+const HTTP2_FLAG: &str = "http2";
+const HTTP2_PRIOR_KNOWLEDGE_FLAG: &str = "http2-prior-knowledge";
+const MAX_STREAMS_FLAG: &str = "max-streams";
+const CACERT_FLAG: &str = "cacert";
+const CERT_FLAG: &str = "cert";
+const CERT_TYPE_FLAG: &str = "cert-type";
+const CERT_PASS_FLAG: &str = "cert-pass";
+const KEY_FLAG: &str = "key";
+const INSECURE_FLAG: &str = "insecure";
+const TLS_MIN_VERSION_FLAG: &str = "tls-min-version";
+const RESOLVE_FLAG: &str = "resolve";
+const PROXY_FLAG: &str = "proxy";
+const NO_PROXY_FLAG: &str = "no-proxy";
+const MAX_REDIRECTS_FLAG: &str = "max-redirects";
+const SAVE_RESPONSES_FLAG: &str = "save-responses";
+const SAVE_SAMPLE_FLAG: &str = "save-sample";
+const TRACEPARENT_FLAG: &str = "traceparent";
+const OTLP_ENDPOINT_FLAG: &str = "otlp-endpoint";
+const BEARER_FLAG: &str = "bearer";
+const BEARER_ENV_FLAG: &str = "bearer-env";
+const BEARER_FILE_FLAG: &str = "bearer-file";
+const API_KEY_FLAG: &str = "api-key";
+const API_KEY_ENV_FLAG: &str = "api-key-env";
+const API_KEY_FILE_FLAG: &str = "api-key-file";
+const API_KEY_HEADER_FLAG: &str = "api-key-header";
+const OAUTH2_TOKEN_URL_FLAG: &str = "oauth2-token-url";
+const OAUTH2_CLIENT_ID_FLAG: &str = "oauth2-client-id";
+const OAUTH2_CLIENT_SECRET_FLAG: &str = "oauth2-client-secret";
+const OAUTH2_CLIENT_SECRET_ENV_FLAG: &str = "oauth2-client-secret-env";
+const OAUTH2_CLIENT_SECRET_FILE_FLAG: &str = "oauth2-client-secret-file";
+const OAUTH2_SCOPE_FLAG: &str = "oauth2-scope";
 
 fn cmd<'a, 'b>() -> App<'a, 'b> {
     let app = App::new("burst")
         .version("0.1-dev")
         .about("Sends bursts of requests to a specified host.");
 
+    // Without a subcommand burst sends load, so the flags of `run` are also
+    // the flags of burst itself.
+    run_args(app)
+        .setting(AppSettings::SubcommandsNegateReqs)
+        .subcommand(run_args(SubCommand::with_name(RUN_CMD).about(
+            "Sends bursts of requests to a specified host. This is what burst does without a subcommand.",
+        )))
+        .subcommand(report_cmd())
+        .subcommand(compare_cmd())
+        .subcommand(validate_cmd())
+        .subcommand(agent_cmd())
+        .subcommand(coordinator_cmd())
+}
+
+fn run_args<'a, 'b>(app: App<'a, 'b>) -> App<'a, 'b> {
+    let config_arg = Arg::with_name(CONFIG_FLAG)
+        .long(CONFIG_FLAG)
+        .takes_value(true)
+        .value_name("path")
+        .help("Read flags from a TOML file, where each key is the long name of a flag.")
+        .required(false);
+
+    let output_arg = output_arg();
+
+    let html_report_arg = html_report_arg();
+
     let load_arg = Arg::with_name(LOAD_FLAG)
         .long(LOAD_FLAG)
         .short("l")
         .takes_value(true)
         .default_value("100")
         .help("Amount of requests to send.")
-        .required(false);
+        .required(false)
+        .overrides_with(LOAD_FLAG);
 
     let workers_arg = Arg::with_name(WORKERS_FLAG)
         .long(WORKERS_FLAG)
@@ -44,8 +159,22 @@ fn cmd<'a, 'b>() -> App<'a, 'b> {
         .takes_value(true)
         .default_value("10")
         .help("Number of workers to run in parallel.")
+        .required(false)
+        .overrides_with(WORKERS_FLAG);
+
+    let threads_arg = Arg::with_name(THREADS_FLAG)
+        .long(THREADS_FLAG)
+        .takes_value(true)
+        .help("Number of threads burst runs on. Defaults to one per core.")
+        .validator(at_least_one)
         .required(false);
 
+    let single_threaded_arg = Arg::with_name(SINGLE_THREADED_FLAG)
+        .long(SINGLE_THREADED_FLAG)
+        .help("Run burst on a single thread.")
+        .required(false)
+        .conflicts_with(THREADS_FLAG);
+
     let duration_arg = Arg::with_name(DURATION_FLAG)
         .long(DURATION_FLAG)
         .short("d")
@@ -74,8 +203,23 @@ The actual running time will vary depending on the load, workers and the time it
         .long(HOST_FLAG)
         .short("h")
         .takes_value(true)
-        .help("Host header to send the requests to.")
-        .required(true);
+        .help("Host header to send the requests to. Can be set multiple times to spread the load across several hosts.")
+        .multiple(true)
+        .number_of_values(1)
+        .required_unless(HOSTS_FILE_FLAG);
+
+    let hosts_file_arg = Arg::with_name(HOSTS_FILE_FLAG)
+        .long(HOSTS_FILE_FLAG)
+        .takes_value(true)
+        .help("Read the hosts to send the requests to from a file, one per line.")
+        .required(false);
+
+    let strategy_arg = Arg::with_name(STRATEGY_FLAG)
+        .long(STRATEGY_FLAG)
+        .takes_value(true)
+        .help("How requests are spread across hosts. One of 'round-robin', 'random', or 'least-in-flight'.")
+        .default_value("round-robin")
+        .required(false);
 
     let method_arg = Arg::with_name(METHOD_FLAG)
         .long(METHOD_FLAG)
@@ -100,6 +244,46 @@ The actual running time will vary depending on the load, workers and the time it
         .required(false)
         .conflicts_with(BODY_FLAG);
 
+    let stream_body_arg = Arg::with_name(STREAM_BODY_FLAG)
+        .long(STREAM_BODY_FLAG)
+        .help(
+            "Stream the --body-file from disk for every request instead of loading it into memory.",
+        )
+        .required(false)
+        .requires(BODY_FILE_FLAG)
+        .conflicts_with(BODY_ENCODING_FLAG);
+
+    let form_arg = Arg::with_name(FORM_FLAG)
+        .long(FORM_FLAG)
+        .short("F")
+        .takes_value(true)
+        .multiple(true)
+        .number_of_values(1)
+        .value_name("key=value")
+        .help("Send a multipart/form-data body. Use key=@path to upload a file. Can be repeated.")
+        .required(false)
+        .conflicts_with_all(&[BODY_FLAG, BODY_FILE_FLAG]);
+
+    let data_urlencode_arg = Arg::with_name(DATA_URLENCODE_FLAG)
+        .long(DATA_URLENCODE_FLAG)
+        .takes_value(true)
+        .multiple(true)
+        .number_of_values(1)
+        .help("URL-encode data and send it as an application/x-www-form-urlencoded body. Use name=content or name@path. Can be repeated.")
+        .required(false)
+        .conflicts_with_all(&[BODY_FLAG, BODY_FILE_FLAG, FORM_FLAG]);
+
+    let body_encoding_arg = Arg::with_name(BODY_ENCODING_FLAG)
+        .long(BODY_ENCODING_FLAG)
+        .takes_value(true)
+        .help("Compress the request body and set Content-Encoding. One of 'gzip', 'deflate', 'br', or 'zstd'.")
+        .required(false);
+
+    let compressed_arg = Arg::with_name(COMPRESSED_FLAG)
+        .long(COMPRESSED_FLAG)
+        .help("Ask for a compressed response and decompress it.")
+        .required(false);
+
     let user_arg = Arg::with_name(USER_FLAG)
         .long(USER_FLAG)
         .short("u")
@@ -111,7 +295,24 @@ The actual running time will vary depending on the load, workers and the time it
         .long(PASS_FLAG)
         .short("p")
         .takes_value(true)
-        .help("Password for basic authentication.")
+        .help("Password for basic authentication. Requires --user to be set. Prefer --pass-env or --pass-file, the password is prompted for when none of them is set and burst is run from a terminal.")
+        .requires(USER_FLAG)
+        .required(false);
+
+    let pass_env_arg = Arg::with_name(PASS_ENV_FLAG)
+        .long(PASS_ENV_FLAG)
+        .takes_value(true)
+        .value_name("var")
+        .help("Read the basic authentication password from an environment variable.")
+        .requires(USER_FLAG)
+        .required(false);
+
+    let pass_file_arg = Arg::with_name(PASS_FILE_FLAG)
+        .long(PASS_FILE_FLAG)
+        .takes_value(true)
+        .value_name("path")
+        .help("Read the basic authentication password from a file.")
+        .requires(USER_FLAG)
         .required(false);
 
     let exact_arg = Arg::with_name(EXACT_FLAG)
@@ -127,52 +328,939 @@ The actual running time will vary depending on the load, workers and the time it
         .help("Enable verbose mode.")
         .required(false);
 
-    app.arg(load_arg)
+    let http2_arg = Arg::with_name(HTTP2_FLAG)
+        .long(HTTP2_FLAG)
+        .help("Offer HTTP/2 through ALPN, falling back to HTTP/1.1 if the host doesn't support it.")
+        .required(false);
+
+    let http2_prior_knowledge_arg = Arg::with_name(HTTP2_PRIOR_KNOWLEDGE_FLAG)
+        .long(HTTP2_PRIOR_KNOWLEDGE_FLAG)
+        .help("Use HTTP/2 without negotiation. Required for cleartext (h2c) hosts.")
+        .required(false)
+        .conflicts_with(HTTP2_FLAG);
+
+    let max_streams_arg = Arg::with_name(MAX_STREAMS_FLAG)
+        .long(MAX_STREAMS_FLAG)
+        .takes_value(true)
+        .help("Maximum amount of concurrent HTTP/2 streams per connection. Requires --http2 or --http2-prior-knowledge to be set.")
+        .required(false);
+
+    let cacert_arg = Arg::with_name(CACERT_FLAG)
+        .long(CACERT_FLAG)
+        .takes_value(true)
+        .help("PEM certificate of a CA to trust on top of the system ones.")
+        .required(false);
+
+    let cert_arg = Arg::with_name(CERT_FLAG)
+        .long(CERT_FLAG)
+        .takes_value(true)
+        .help("Client certificate for mutual TLS.")
+        .required(false);
+
+    let cert_type_arg = Arg::with_name(CERT_TYPE_FLAG)
+        .long(CERT_TYPE_FLAG)
+        .takes_value(true)
+        .requires(CERT_FLAG)
+        .help("Format of the client certificate. One of 'pem' or 'p12'. PEM certificates require --key to be set. Defaults to 'pem'.")
+        .required(false);
+
+    let cert_pass_arg = Arg::with_name(CERT_PASS_FLAG)
+        .long(CERT_PASS_FLAG)
+        .takes_value(true)
+        .requires(CERT_FLAG)
+        .help("Password for a PKCS#12 client certificate.")
+        .required(false);
+
+    let key_arg = Arg::with_name(KEY_FLAG)
+        .long(KEY_FLAG)
+        .takes_value(true)
+        .requires(CERT_FLAG)
+        .help("PKCS#8 PEM private key of the client certificate.")
+        .required(false);
+
+    let insecure_arg = Arg::with_name(INSECURE_FLAG)
+        .long(INSECURE_FLAG)
+        .short("k")
+        .help("Skip TLS certificate and hostname verification. Only meant for test environments.")
+        .required(false);
+
+    let tls_min_version_arg = Arg::with_name(TLS_MIN_VERSION_FLAG)
+        .long(TLS_MIN_VERSION_FLAG)
+        .takes_value(true)
+        .help("Minimum TLS version to accept. One of '1.0', '1.1', or '1.2'.")
+        .required(false);
+
+    let resolve_arg = Arg::with_name(RESOLVE_FLAG)
+        .long(RESOLVE_FLAG)
+        .takes_value(true)
+        .value_name("host:port:addr[,addr]...")
+        .multiple(true)
+        .number_of_values(1)
+        .help("Connect to the given addresses instead of resolving the host through DNS, keeping the Host header and SNI intact. The port has to be the one the host is sent requests on. Can be set multiple times.")
+        .required(false);
+
+    let proxy_arg = Arg::with_name(PROXY_FLAG)
+        .long(PROXY_FLAG)
+        .short("x")
+        .takes_value(true)
+        .help("HTTP, HTTPS or SOCKS5 proxy to send every request through. Defaults to the proxies set in the HTTP_PROXY and HTTPS_PROXY environment variables.")
+        .required(false);
+
+    let no_proxy_arg = Arg::with_name(NO_PROXY_FLAG)
+        .long(NO_PROXY_FLAG)
+        .takes_value(true)
+        .help("Comma separated list of hosts that bypass the proxy, or '*' to bypass it for every host. Defaults to the NO_PROXY environment variable.")
+        .required(false);
+
+    let max_redirects_arg = Arg::with_name(MAX_REDIRECTS_FLAG)
+        .long(MAX_REDIRECTS_FLAG)
+        .takes_value(true)
+        .default_value("10")
+        .help(
+            "Maximum amount of redirects to follow per request. Set to 0 to not follow redirects.",
+        )
+        .required(false);
+
+    let save_responses_arg = Arg::with_name(SAVE_RESPONSES_FLAG)
+        .long(SAVE_RESPONSES_FLAG)
+        .takes_value(true)
+        .value_name("dir")
+        .help("Save a sample of response bodies to the given directory.")
+        .required(false);
+
+    let save_sample_arg = Arg::with_name(SAVE_SAMPLE_FLAG)
+        .long(SAVE_SAMPLE_FLAG)
+        .takes_value(true)
+        .requires(SAVE_RESPONSES_FLAG)
+        .help("Amount of response bodies to save when using --save-responses. Defaults to 10.")
+        .required(false);
+
+    let traceparent_arg = Arg::with_name(TRACEPARENT_FLAG)
+        .long(TRACEPARENT_FLAG)
+        .help("Send a W3C traceparent header with every request, built from the request ID.")
+        .required(false);
+
+    let otlp_endpoint_arg = Arg::with_name(OTLP_ENDPOINT_FLAG)
+        .long(OTLP_ENDPOINT_FLAG)
+        .takes_value(true)
+        .value_name("url")
+        .help("Export a client span for every request to an OTLP/HTTP collector, e.g. http://localhost:4318. Spans are exported in batches during the run, and dropped if the collector falls behind. Implies --traceparent.")
+        .required(false);
+
+    let bearer_arg = Arg::with_name(BEARER_FLAG)
+        .long(BEARER_FLAG)
+        .takes_value(true)
+        .help("Token for bearer authentication.")
+        .required(false);
+
+    let bearer_env_arg = Arg::with_name(BEARER_ENV_FLAG)
+        .long(BEARER_ENV_FLAG)
+        .takes_value(true)
+        .value_name("var")
+        .help("Read the bearer authentication token from an environment variable.")
+        .required(false);
+
+    let bearer_file_arg = Arg::with_name(BEARER_FILE_FLAG)
+        .long(BEARER_FILE_FLAG)
+        .takes_value(true)
+        .value_name("path")
+        .help("Read the bearer authentication token from a file.")
+        .required(false);
+
+    let api_key_arg = Arg::with_name(API_KEY_FLAG)
+        .long(API_KEY_FLAG)
+        .takes_value(true)
+        .help("API key sent in the header set by --api-key-header.")
+        .required(false);
+
+    let api_key_env_arg = Arg::with_name(API_KEY_ENV_FLAG)
+        .long(API_KEY_ENV_FLAG)
+        .takes_value(true)
+        .value_name("var")
+        .help("Read the API key from an environment variable.")
+        .required(false);
+
+    let api_key_file_arg = Arg::with_name(API_KEY_FILE_FLAG)
+        .long(API_KEY_FILE_FLAG)
+        .takes_value(true)
+        .value_name("path")
+        .help("Read the API key from a file.")
+        .required(false);
+
+    let api_key_header_arg = Arg::with_name(API_KEY_HEADER_FLAG)
+        .long(API_KEY_HEADER_FLAG)
+        .takes_value(true)
+        .help("Header to send the API key in. Defaults to 'X-API-Key'.")
+        .required(false);
+
+    let oauth2_token_url_arg = Arg::with_name(OAUTH2_TOKEN_URL_FLAG)
+        .long(OAUTH2_TOKEN_URL_FLAG)
+        .takes_value(true)
+        .requires(OAUTH2_CLIENT_ID_FLAG)
+        .help("Token endpoint for OAuth2 client credentials authentication. The token is fetched before sending load and refreshed when it expires.")
+        .required(false);
+
+    let oauth2_client_id_arg = Arg::with_name(OAUTH2_CLIENT_ID_FLAG)
+        .long(OAUTH2_CLIENT_ID_FLAG)
+        .takes_value(true)
+        .help("OAuth2 client ID.")
+        .required(false);
+
+    let oauth2_client_secret_arg = Arg::with_name(OAUTH2_CLIENT_SECRET_FLAG)
+        .long(OAUTH2_CLIENT_SECRET_FLAG)
+        .takes_value(true)
+        .help("OAuth2 client secret. It is prompted for when no client secret flag is set and burst is run from a terminal.")
+        .required(false);
+
+    let oauth2_client_secret_env_arg = Arg::with_name(OAUTH2_CLIENT_SECRET_ENV_FLAG)
+        .long(OAUTH2_CLIENT_SECRET_ENV_FLAG)
+        .takes_value(true)
+        .value_name("var")
+        .help("Read the OAuth2 client secret from an environment variable.")
+        .required(false);
+
+    let oauth2_client_secret_file_arg = Arg::with_name(OAUTH2_CLIENT_SECRET_FILE_FLAG)
+        .long(OAUTH2_CLIENT_SECRET_FILE_FLAG)
+        .takes_value(true)
+        .value_name("path")
+        .help("Read the OAuth2 client secret from a file.")
+        .required(false);
+
+    // Only one authentication scheme can be used at a time, and each secret
+    // can only come from one place.
+    let auth_group = ArgGroup::with_name("auth").args(&[
+        USER_FLAG,
+        BEARER_FLAG,
+        BEARER_ENV_FLAG,
+        BEARER_FILE_FLAG,
+        API_KEY_FLAG,
+        API_KEY_ENV_FLAG,
+        API_KEY_FILE_FLAG,
+        OAUTH2_TOKEN_URL_FLAG,
+    ]);
+    let pass_group =
+        ArgGroup::with_name("pass-source").args(&[PASS_FLAG, PASS_ENV_FLAG, PASS_FILE_FLAG]);
+    let oauth2_client_secret_group = ArgGroup::with_name("oauth2-client-secret-source").args(&[
+        OAUTH2_CLIENT_SECRET_FLAG,
+        OAUTH2_CLIENT_SECRET_ENV_FLAG,
+        OAUTH2_CLIENT_SECRET_FILE_FLAG,
+    ]);
+
+    let oauth2_scope_arg = Arg::with_name(OAUTH2_SCOPE_FLAG)
+        .long(OAUTH2_SCOPE_FLAG)
+        .takes_value(true)
+        .help("Scope to request the OAuth2 token for.")
+        .required(false);
+
+    app.arg(config_arg)
+        .arg(output_arg)
+        .arg(html_report_arg)
+        .arg(histogram_arg())
+        .arg(histogram_step_arg())
+        .arg(load_arg)
         .arg(duration_arg)
         .arg(interval_arg)
         .arg(exact_arg)
         .arg(timeout_arg)
         .arg(host_arg)
+        .arg(hosts_file_arg)
+        .arg(strategy_arg)
         .arg(workers_arg)
+        .arg(threads_arg)
+        .arg(single_threaded_arg)
         .arg(pass_arg)
+        .arg(pass_env_arg)
+        .arg(pass_file_arg)
         .arg(verbose_arg)
         .arg(user_arg)
         .arg(method_arg)
         .arg(body_arg)
         .arg(body_file_arg)
+        .arg(stream_body_arg)
+        .arg(form_arg)
+        .arg(data_urlencode_arg)
+        .arg(body_encoding_arg)
+        .arg(compressed_arg)
+        .arg(http2_arg)
+        .arg(http2_prior_knowledge_arg)
+        .arg(max_streams_arg)
+        .arg(cacert_arg)
+        .arg(cert_arg)
+        .arg(cert_type_arg)
+        .arg(cert_pass_arg)
+        .arg(key_arg)
+        .arg(insecure_arg)
+        .arg(tls_min_version_arg)
+        .arg(resolve_arg)
+        .arg(proxy_arg)
+        .arg(no_proxy_arg)
+        .arg(max_redirects_arg)
+        .arg(save_responses_arg)
+        .arg(save_sample_arg)
+        .arg(traceparent_arg)
+        .arg(otlp_endpoint_arg)
+        .arg(bearer_arg)
+        .arg(bearer_env_arg)
+        .arg(bearer_file_arg)
+        .arg(api_key_arg)
+        .arg(api_key_env_arg)
+        .arg(api_key_file_arg)
+        .arg(api_key_header_arg)
+        .arg(oauth2_token_url_arg)
+        .arg(oauth2_client_id_arg)
+        .arg(oauth2_client_secret_arg)
+        .arg(oauth2_client_secret_env_arg)
+        .arg(oauth2_client_secret_file_arg)
+        .arg(oauth2_scope_arg)
+        .group(auth_group)
+        .group(pass_group)
+        .group(oauth2_client_secret_group)
 }
 
-pub fn burst_app() -> Client {
-    let app = cmd();
+fn output_arg<'a, 'b>() -> Arg<'a, 'b> {
+    Arg::with_name(OUTPUT_FLAG)
+        .long(OUTPUT_FLAG)
+        .short("o")
+        .takes_value(true)
+        .value_name("path")
+        .help("Save the results as JSON, to render them again with `burst report`.")
+        .required(false)
+}
 
-    let matches = app.get_matches();
+fn html_report_arg<'a, 'b>() -> Arg<'a, 'b> {
+    Arg::with_name(HTML_REPORT_FLAG)
+        .long(HTML_REPORT_FLAG)
+        .takes_value(true)
+        .value_name("path")
+        .help("Save the results as an HTML page with charts, which can be viewed offline.")
+        .required(false)
+}
+
+fn histogram_arg<'a, 'b>() -> Arg<'a, 'b> {
+    Arg::with_name(HISTOGRAM_FLAG)
+        .long(HISTOGRAM_FLAG)
+        .takes_value(true)
+        .default_value("quantize")
+        .help("How to bucket the latency histogram: 'quantize' for power-of-two buckets or 'linear' for buckets of the same width.")
+        .required(false)
+}
+
+fn histogram_step_arg<'a, 'b>() -> Arg<'a, 'b> {
+    Arg::with_name(HISTOGRAM_STEP_FLAG)
+        .long(HISTOGRAM_STEP_FLAG)
+        .takes_value(true)
+        .value_name("ms")
+        .help("Width of the buckets of a linear histogram in milliseconds. Picked from the spread of the latencies by default. At most 100 buckets are shown, the last counting every slower latency.")
+        .required(false)
+}
+
+fn report_cmd<'a, 'b>() -> App<'a, 'b> {
+    let file_arg = Arg::with_name(FILE_ARG)
+        .help("Results saved with --output.")
+        .required(true);
+
+    SubCommand::with_name(REPORT_CMD)
+        .about("Renders the summary of a run saved with --output.")
+        .arg(file_arg)
+        .arg(html_report_arg())
+        .arg(histogram_arg())
+        .arg(histogram_step_arg())
+}
+
+fn compare_cmd<'a, 'b>() -> App<'a, 'b> {
+    let baseline_arg = Arg::with_name(BASELINE_ARG)
+        .help("Results saved with --output to compare against.")
+        .required(true);
+
+    let candidate_arg = Arg::with_name(CANDIDATE_ARG)
+        .help("Results saved with --output to check for regressions.")
+        .required(true);
+
+    let tolerance_arg = Arg::with_name(TOLERANCE_FLAG)
+        .long(TOLERANCE_FLAG)
+        .takes_value(true)
+        .value_name("percent")
+        .default_value("5")
+        .validator(tolerance)
+        .help("How much worse the candidate may do before it counts as a regression. Error rates may grow by this many percentage points.")
+        .required(false);
+
+    let alpha_arg = Arg::with_name(ALPHA_FLAG)
+        .long(ALPHA_FLAG)
+        .takes_value(true)
+        .default_value("0.05")
+        .validator(alpha)
+        .help("Significance level below which a change isn't put down to chance.")
+        .required(false);
+
+    SubCommand::with_name(COMPARE_CMD)
+        .about("Compares two runs saved with --output and exits with an error if the second regressed.")
+        .arg(baseline_arg)
+        .arg(candidate_arg)
+        .arg(tolerance_arg)
+        .arg(alpha_arg)
+}
+
+fn validate_cmd<'a, 'b>() -> App<'a, 'b> {
+    let file_arg = Arg::with_name(FILE_ARG)
+        .help("TOML file to check, as read by --config.")
+        .required(true);
+
+    SubCommand::with_name(VALIDATE_CMD)
+        .about("Checks the flags in a config file without sending any load.")
+        .arg(file_arg)
+}
+
+fn agent_cmd<'a, 'b>() -> App<'a, 'b> {
+    let listen_arg = Arg::with_name(LISTEN_FLAG)
+        .long(LISTEN_FLAG)
+        .takes_value(true)
+        .value_name("addr")
+        .default_value("127.0.0.1:7878")
+        .help("Address to listen on for a coordinator.")
+        .required(false);
+
+    SubCommand::with_name(AGENT_CMD)
+        .about("Waits for a coordinator to hand it load to send.")
+        .arg(listen_arg)
+        .args(&token_args())
+        .group(token_group())
+}
+
+fn coordinator_cmd<'a, 'b>() -> App<'a, 'b> {
+    let agent_arg = Arg::with_name(AGENT_FLAG)
+        .long(AGENT_FLAG)
+        .short("a")
+        .takes_value(true)
+        .multiple(true)
+        .number_of_values(1)
+        .value_name("addr")
+        .help("Address of an agent to send load from. Can be set multiple times.")
+        .required(true);
+
+    let run_args = Arg::with_name(RUN_ARGS)
+        .multiple(true)
+        .last(true)
+        .help("Flags of the run to spread across the agents. The load and workers are split between them.")
+        .required(true);
+
+    SubCommand::with_name(COORDINATOR_CMD)
+        .about("Spreads a run across several agents and merges their results.")
+        .arg(agent_arg)
+        .args(&token_args())
+        .group(token_group())
+        .arg(output_arg())
+        .arg(html_report_arg())
+        .arg(run_args)
+}
+
+// The token agents and coordinators share, so an agent only takes load
+// from its coordinator.
+fn token_args<'a, 'b>() -> [Arg<'a, 'b>; 3] {
+    [
+        Arg::with_name(TOKEN_FLAG)
+            .long(TOKEN_FLAG)
+            .takes_value(true)
+            .help(
+                "Token the coordinator and its agents share. Prefer --token-env or --token-file.",
+            ),
+        Arg::with_name(TOKEN_ENV_FLAG)
+            .long(TOKEN_ENV_FLAG)
+            .takes_value(true)
+            .value_name("var")
+            .help(
+                "Read the token the coordinator and its agents share from an environment variable.",
+            ),
+        Arg::with_name(TOKEN_FILE_FLAG)
+            .long(TOKEN_FILE_FLAG)
+            .takes_value(true)
+            .value_name("path")
+            .help("Read the token the coordinator and its agents share from a file."),
+    ]
+}
+
+fn token_group<'a>() -> ArgGroup<'a> {
+    ArgGroup::with_name("token-source")
+        .args(&[TOKEN_FLAG, TOKEN_ENV_FLAG, TOKEN_FILE_FLAG])
+        .required(true)
+}
+
+fn token(matches: &ArgMatches) -> Secret {
+    secret_value(matches, TOKEN_FLAG, TOKEN_ENV_FLAG, TOKEN_FILE_FLAG)
+        .unwrap_or_else(|e| panic!("{}", e))
+        .unwrap_or_else(validate_flag_error!(TOKEN_FLAG))
+}
+
+// Secrets can be set through the flag itself, or kept out of the shell
+// history and `ps` output by reading them from an environment variable or
+// a file instead.
+fn secret_value(
+    matches: &ArgMatches,
+    flag: &str,
+    env_flag: &str,
+    file_flag: &str,
+) -> Result<Option<Secret>, String> {
+    if let Some(secret) = matches.value_of(flag) {
+        Ok(Some(Secret::new(secret.to_string())))
+    } else if let Some(var) = matches.value_of(env_flag) {
+        Secret::from_env(var).map(Some)
+    } else {
+        matches
+            .value_of(file_flag)
+            .map(Secret::from_file)
+            .transpose()
+    }
+}
+
+pub fn matches() -> ArgMatches<'static> {
+    let args = config::expand(env::args().collect()).unwrap_or_else(|e| {
+        clap::Error::with_description(&e, clap::ErrorKind::InvalidValue).exit()
+    });
+    cmd().get_matches_from(args)
+}
+
+/// Parses the flags of a run a coordinator handed to an agent.
+pub fn matches_from(args: &[String]) -> clap::Result<ArgMatches<'static>> {
+    let matches = cmd()
+        .get_matches_from_safe(iter::once(String::from("burst")).chain(args.iter().cloned()))?;
+    if let Some(name) = matches.subcommand_name() {
+        return Err(clap::Error::with_description(
+            &format!("{} can't be used as part of a run.", name),
+            clap::ErrorKind::UnexpectedMultipleUsage,
+        ));
+    }
+    Ok(matches)
+}
+
+// Flags that read or write files, read environment variables or set up the
+// runtime on the machine the run is sent from.
+const LOCAL_FLAGS: &[&str] = &[
+    OUTPUT_FLAG,
+    HTML_REPORT_FLAG,
+    BODY_FILE_FLAG,
+    HOSTS_FILE_FLAG,
+    PASS_ENV_FLAG,
+    PASS_FILE_FLAG,
+    CACERT_FLAG,
+    CERT_FLAG,
+    KEY_FLAG,
+    SAVE_RESPONSES_FLAG,
+    BEARER_ENV_FLAG,
+    BEARER_FILE_FLAG,
+    API_KEY_ENV_FLAG,
+    API_KEY_FILE_FLAG,
+    OAUTH2_CLIENT_SECRET_ENV_FLAG,
+    OAUTH2_CLIENT_SECRET_FILE_FLAG,
+    THREADS_FLAG,
+    SINGLE_THREADED_FLAG,
+];
+
+/// Checks a run can be handed to an agent. A run can't use the agent's
+/// files or environment, or whoever can reach the agent could read its
+/// files and send them anywhere. Nor can it prompt for a secret, as nobody
+/// is at the agent's terminal to answer, or pick the threads the agent
+/// runs on, as its runtime is already running.
+pub fn check_job(matches: &ArgMatches) -> Result<(), String> {
+    if let Some(flag) = LOCAL_FLAGS.iter().find(|&&flag| matches.is_present(flag)) {
+        return Err(format!(
+            "--{} can't be used in a distributed run, as it would use the agent's files, environment or runtime.",
+            flag
+        ));
+    }
+
+    let uploads = matches
+        .values_of(FORM_FLAG)
+        .into_iter()
+        .flatten()
+        .any(|field| body::multipart_file(field).is_some());
+    let reads = matches
+        .values_of(DATA_URLENCODE_FLAG)
+        .into_iter()
+        .flatten()
+        .any(|value| body::urlencoded_file(value).is_some());
+    if uploads || reads {
+        return Err(String::from(
+            "Form fields can't be read from files in a distributed run, as they'd be read from the agent's files.",
+        ));
+    }
+
+    if matches.is_present(USER_FLAG) && !matches.is_present(PASS_FLAG) {
+        return Err(format!(
+            "--{} requires --{} in a distributed run, as agents can't prompt for it.",
+            USER_FLAG, PASS_FLAG
+        ));
+    }
+    if matches.is_present(OAUTH2_TOKEN_URL_FLAG) && !matches.is_present(OAUTH2_CLIENT_SECRET_FLAG) {
+        return Err(format!(
+            "--{} requires --{} in a distributed run, as agents can't prompt for it.",
+            OAUTH2_TOKEN_URL_FLAG, OAUTH2_CLIENT_SECRET_FLAG
+        ));
+    }
+
+    Ok(())
+}
+
+// Flags that take a whole number.
+const NUMBER_FLAGS: &[&str] = &[
+    LOAD_FLAG,
+    WORKERS_FLAG,
+    TIMEOUT_FLAG,
+    DURATION_FLAG,
+    INTERVAL_FLAG,
+    MAX_STREAMS_FLAG,
+    MAX_REDIRECTS_FLAG,
+    SAVE_SAMPLE_FLAG,
+];
+
+/// Checks the flags of a run make sense without sending anything, prompting
+/// for secrets or panicking. clap only checks the flags are known and have
+/// values; everything burst would otherwise find out while building the
+/// client is checked here.
+pub fn check_run(matches: &ArgMatches) -> Result<(), String> {
+    for &flag in NUMBER_FLAGS {
+        if let Some(value) = matches.value_of(flag) {
+            number::<u64>(flag, value)?;
+        }
+    }
+
+    let hosts = hosts(matches)?;
+    strategy(
+        matches
+            .value_of(STRATEGY_FLAG)
+            .unwrap_or_else(validate_flag_error!(STRATEGY_FLAG)),
+    )?;
+    method(
+        matches
+            .value_of(METHOD_FLAG)
+            .unwrap_or_else(validate_flag_error!(METHOD_FLAG)),
+    )?;
+    http::resolve_overrides(
+        matches.values_of(RESOLVE_FLAG).into_iter().flatten(),
+        &hosts,
+    )?;
+
+    ProxyOptions::new(
+        matches.value_of(PROXY_FLAG),
+        matches.value_of(NO_PROXY_FLAG),
+    )?;
+
+    // Secrets are read from their environment variables and files, but
+    // never prompted for.
+    for (flag, env_flag, file_flag) in [
+        (PASS_FLAG, PASS_ENV_FLAG, PASS_FILE_FLAG),
+        (BEARER_FLAG, BEARER_ENV_FLAG, BEARER_FILE_FLAG),
+        (API_KEY_FLAG, API_KEY_ENV_FLAG, API_KEY_FILE_FLAG),
+        (
+            OAUTH2_CLIENT_SECRET_FLAG,
+            OAUTH2_CLIENT_SECRET_ENV_FLAG,
+            OAUTH2_CLIENT_SECRET_FILE_FLAG,
+        ),
+    ] {
+        secret_value(matches, flag, env_flag, file_flag)?;
+    }
+    if matches.is_present(API_KEY_FLAG)
+        || matches.is_present(API_KEY_ENV_FLAG)
+        || matches.is_present(API_KEY_FILE_FLAG)
+    {
+        api_key_header(matches)?;
+    }
+    if let Some(url) = matches.value_of(OAUTH2_TOKEN_URL_FLAG) {
+        Url::parse(url).map_err(|e| format!("{} is not a valid OAuth2 token URL: {}", url, e))?;
+    }
+
+    if let Some(path) = matches.value_of(BODY_FILE_FLAG) {
+        readable(path)?;
+    }
+    for field in matches.values_of(FORM_FLAG).into_iter().flatten() {
+        body::split_field(field)?;
+        if let Some(path) = body::multipart_file(field) {
+            readable(path)?;
+        }
+    }
+    for value in matches.values_of(DATA_URLENCODE_FLAG).into_iter().flatten() {
+        if let Some(path) = body::urlencoded_file(value) {
+            readable(path)?;
+        }
+    }
+    body_encoding(matches)?;
+
+    max_streams(matches)?;
+    if let Some(path) = matches.value_of(CACERT_FLAG) {
+        tls::load_ca_cert(path)?;
+    }
+    let cert_type = cert_type(matches)?;
+    if let Some(cert) = matches.value_of(CERT_FLAG) {
+        tls::load_identity(
+            cert_type,
+            cert,
+            matches.value_of(KEY_FLAG),
+            matches.value_of(CERT_PASS_FLAG).unwrap_or(""),
+        )?;
+    }
+    if let Some(version) = matches.value_of(TLS_MIN_VERSION_FLAG) {
+        tls::parse_version(version)?;
+    }
+    if let Some(endpoint) = matches.value_of(OTLP_ENDPOINT_FLAG) {
+        trace::traces_url(endpoint)?;
+    }
+    scale(matches)?;
+
+    Ok(())
+}
+
+// Files the results of a run are saved to.
+fn outputs(matches: &ArgMatches) -> Outputs {
+    Outputs {
+        json: matches.value_of(OUTPUT_FLAG).map(PathBuf::from),
+        html: matches.value_of(HTML_REPORT_FLAG).map(PathBuf::from),
+    }
+}
+
+// How the latency histogram at the end of the summary is bucketed.
+fn scale(matches: &ArgMatches) -> Result<Scale, String> {
+    let step = matches
+        .value_of(HISTOGRAM_STEP_FLAG)
+        .map(|step| {
+            step.parse()
+                .ok()
+                .filter(|ms: &f64| ms.is_finite() && *ms > 0.0)
+                .map(|ms| Duration::from_secs_f64(ms / 1000.0))
+                .ok_or_else(|| format!("{} is not a valid histogram step.", step))
+        })
+        .transpose()?;
+    let histogram = matches
+        .value_of(HISTOGRAM_FLAG)
+        .unwrap_or_else(validate_flag_error!(HISTOGRAM_FLAG));
+    match histogram {
+        "quantize" => Ok(Scale::Quantize),
+        "linear" => Ok(Scale::Linear(step)),
+        _ => Err(format!(
+            "{} is not a supported histogram. Use one of: 'quantize' or 'linear'.",
+            histogram
+        )),
+    }
+}
+
+/// What burst was asked to do.
+pub enum Command {
+    Run(Client),
+    Report(Report, Outputs),
+    Compare(Comparison),
+    Validate(Result<String, String>),
+    Agent(SocketAddr, Secret),
+    Coordinator(Coordinator),
+}
+
+pub fn command(matches: &ArgMatches) -> Command {
+    match matches.subcommand() {
+        (RUN_CMD, Some(matches)) => Command::Run(run(matches)),
+        (REPORT_CMD, Some(matches)) => {
+            let path = matches
+                .value_of(FILE_ARG)
+                .unwrap_or_else(validate_flag_error!(FILE_ARG));
+            let mut report = load_report(path);
+            report.set_scale(scale(matches).unwrap_or_else(|e| invalid(&e)));
+            let outputs = Outputs {
+                json: None,
+                html: matches.value_of(HTML_REPORT_FLAG).map(PathBuf::from),
+            };
+            Command::Report(report, outputs)
+        }
+        (COMPARE_CMD, Some(matches)) => {
+            let load = |arg| {
+                load_report(
+                    matches
+                        .value_of(arg)
+                        .unwrap_or_else(validate_flag_error!(arg)),
+                )
+            };
+            // Both are checked by their validators.
+            let tolerance: f64 = matches
+                .value_of(TOLERANCE_FLAG)
+                .unwrap_or_else(validate_flag_error!(TOLERANCE_FLAG))
+                .parse()
+                .unwrap();
+            let alpha: f64 = matches
+                .value_of(ALPHA_FLAG)
+                .unwrap_or_else(validate_flag_error!(ALPHA_FLAG))
+                .parse()
+                .unwrap();
+            Command::Compare(Comparison::new(
+                &load(BASELINE_ARG),
+                &load(CANDIDATE_ARG),
+                tolerance,
+                alpha,
+            ))
+        }
+        (VALIDATE_CMD, Some(matches)) => {
+            let path = matches
+                .value_of(FILE_ARG)
+                .unwrap_or_else(validate_flag_error!(FILE_ARG));
+            Command::Validate(validate(path))
+        }
+        (AGENT_CMD, Some(matches)) => {
+            let addr = matches
+                .value_of(LISTEN_FLAG)
+                .unwrap_or_else(validate_flag_error!(LISTEN_FLAG));
+            let addr = addr
+                .parse()
+                .unwrap_or_else(|e| panic!("{} is not a valid address to listen on: {}", addr, e));
+            Command::Agent(addr, token(matches))
+        }
+        (COORDINATOR_CMD, Some(matches)) => {
+            let agents: Vec<String> = matches
+                .values_of(AGENT_FLAG)
+                .unwrap_or_else(validate_flag_error!(AGENT_FLAG))
+                .map(String::from)
+                .collect();
+            let args: Vec<String> = matches
+                .values_of(RUN_ARGS)
+                .unwrap_or_else(validate_flag_error!(RUN_ARGS))
+                .map(String::from)
+                .collect();
+
+            // Check the run is valid before handing it to the agents.
+            let run = matches_from(&args).unwrap_or_else(|e| e.exit());
+            if let Err(e) = check_job(&run) {
+                clap::Error::with_description(&e, clap::ErrorKind::ArgumentConflict).exit();
+            }
+            if let Err(e) = check_run(&run) {
+                invalid(&e);
+            }
+            let load: usize = run
+                .value_of(LOAD_FLAG)
+                .unwrap_or_else(validate_flag_error!(LOAD_FLAG))
+                .parse()
+                .unwrap();
+            let workers: usize = run
+                .value_of(WORKERS_FLAG)
+                .unwrap_or_else(validate_flag_error!(WORKERS_FLAG))
+                .parse()
+                .unwrap();
+            Command::Coordinator(Coordinator::new(
+                agents,
+                args,
+                load,
+                workers,
+                token(matches),
+                outputs(matches),
+                scale(&run).unwrap_or_else(|e| panic!("{}", e)),
+            ))
+        }
+        _ => Command::Run(run(matches)),
+    }
+}
+
+// Builds the client for a run, after checking its flags make sense.
+fn run(matches: &ArgMatches) -> Client {
+    if let Err(e) = check_run(matches) {
+        invalid(&e);
+    }
+    burst_app(matches)
+}
+
+// Loads results saved with --output, exiting like clap does when they
+// can't be.
+fn load_report(path: &str) -> Report {
+    Report::load(Path::new(path)).unwrap_or_else(|e| invalid(&e))
+}
+
+// Exits with `message`, the way clap reports invalid flags.
+fn invalid(message: &str) -> ! {
+    clap::Error::with_description(message, clap::ErrorKind::InvalidValue).exit()
+}
+
+// Checks a config file is valid TOML and that its flags make a valid run.
+fn validate(path: &str) -> Result<String, String> {
+    let args = config::args(path)?;
+    let matches = matches_from(&args).map_err(|e| e.message)?;
+    check_run(&matches)?;
+    Ok(format!("{} is valid.", path))
+}
+
+// Validates flags that take a count, of which there has to be at least one.
+fn at_least_one(value: String) -> Result<(), String> {
+    match value.parse::<usize>() {
+        Ok(0) => Err(String::from("must be at least 1")),
+        Ok(_) => Ok(()),
+        Err(_) => Err(format!("{} is not a number", value)),
+    }
+}
+
+// Validates --tolerance, a percentage that can't be negative.
+fn tolerance(value: String) -> Result<(), String> {
+    match value.parse::<f64>() {
+        Ok(tolerance) if tolerance.is_finite() && tolerance >= 0.0 => Ok(()),
+        Ok(_) => Err(String::from("must be 0 or more")),
+        Err(_) => Err(format!("{} is not a number", value)),
+    }
+}
+
+// Validates --alpha, a significance level between 0 and 1.
+fn alpha(value: String) -> Result<(), String> {
+    match value.parse::<f64>() {
+        Ok(alpha) if alpha > 0.0 && alpha < 1.0 => Ok(()),
+        Ok(_) => Err(String::from("must be between 0 and 1")),
+        Err(_) => Err(format!("{} is not a number", value)),
+    }
+}
+
+/// Builds the runtime burst runs on, so the threads used by the load
+/// generator itself can be pinned down.
+pub fn runtime(matches: &ArgMatches) -> Runtime {
+    let matches = matches.subcommand_matches(RUN_CMD).unwrap_or(matches);
+    let mut builder = if matches.is_present(SINGLE_THREADED_FLAG) {
+        runtime::Builder::new_current_thread()
+    } else {
+        let mut builder = runtime::Builder::new_multi_thread();
+        if let Some(threads) = matches.value_of(THREADS_FLAG) {
+            builder.worker_threads(threads.parse().unwrap());
+        }
+        builder
+    };
+
+    builder
+        .enable_all()
+        .thread_name("burst-worker")
+        .build()
+        .unwrap_or_else(|e| panic!("Unable to start the runtime: {}", e))
+}
+
+pub fn burst_app(matches: &ArgMatches) -> Client {
+    let fail = |e: String| -> ! { panic!("{}", e) };
 
     let load = matches
         .value_of(LOAD_FLAG)
-        .expect(validate_flag_error!(LOAD_FLAG));
+        .unwrap_or_else(validate_flag_error!(LOAD_FLAG));
 
     let workers = matches
         .value_of(WORKERS_FLAG)
-        .expect(validate_flag_error!(WORKERS_FLAG));
+        .unwrap_or_else(validate_flag_error!(WORKERS_FLAG));
 
     let timeout = matches
         .value_of(TIMEOUT_FLAG)
-        .expect(validate_flag_error!(TIMEOUT_FLAG));
+        .unwrap_or_else(validate_flag_error!(TIMEOUT_FLAG));
 
-    let host = matches
-        .value_of(HOST_FLAG)
-        .expect(validate_flag_error!(HOST_FLAG));
-    let host: String = host.parse().unwrap();
+    let hosts = hosts(matches).unwrap_or_else(|e| fail(e));
 
-    let method = matches
-        .value_of(METHOD_FLAG)
-        .expect(validate_flag_error!(METHOD_FLAG));
+    let strategy = strategy(
+        matches
+            .value_of(STRATEGY_FLAG)
+            .unwrap_or_else(validate_flag_error!(STRATEGY_FLAG)),
+    )
+    .unwrap_or_else(|e| fail(e));
+
+    let http_method = method(
+        matches
+            .value_of(METHOD_FLAG)
+            .unwrap_or_else(validate_flag_error!(METHOD_FLAG)),
+    )
+    .unwrap_or_else(|e| fail(e));
 
-    let workers: usize = workers.parse().unwrap();
-    let timeout: u64 = timeout.parse().unwrap();
+    let workers: usize = number(WORKERS_FLAG, workers).unwrap_or_else(|e| fail(e));
+    let timeout: u64 = number(TIMEOUT_FLAG, timeout).unwrap_or_else(|e| fail(e));
 
-    let reqs: usize = load.parse().unwrap();
-    let requests: Vec<usize> = (0..reqs).collect();
+    let requests: usize = number(LOAD_FLAG, load).unwrap_or_else(|e| fail(e));
 
     // Sets duration and interval to 0 unless the duration flag has been used.
     // In which case load will be sent for a specified duration as opposed
@@ -180,9 +1268,8 @@ pub fn burst_app() -> Client {
     let duration: u64 = if matches.is_present(DURATION_FLAG) {
         let time = matches
             .value_of(DURATION_FLAG)
-            .expect(validate_flag_error!(DURATION_FLAG));
-        let time: u64 = time.parse().unwrap();
-        time
+            .unwrap_or_else(validate_flag_error!(DURATION_FLAG));
+        number(DURATION_FLAG, time).unwrap_or_else(|e| fail(e))
     } else {
         0
     };
@@ -190,80 +1277,167 @@ pub fn burst_app() -> Client {
     let interval: u64 = if matches.is_present(INTERVAL_FLAG) {
         let interval_time = matches
             .value_of(INTERVAL_FLAG)
-            .expect(validate_flag_error!(INTERVAL_FLAG));
-        let interval_time: u64 = interval_time.parse().unwrap();
-        interval_time
+            .unwrap_or_else(validate_flag_error!(INTERVAL_FLAG));
+        number(INTERVAL_FLAG, interval_time).unwrap_or_else(|e| fail(e))
     } else {
         0
     };
 
-    let exact = if matches.is_present(EXACT_FLAG) {
-        true
-    } else {
-        false
-    };
-
-    let user = if matches.is_present(USER_FLAG) {
-        let user_str = matches
-            .value_of(USER_FLAG)
-            .expect(validate_flag_error!(USER_FLAG));
-        let user_str: String = user_str.parse().unwrap();
-        user_str
-    } else {
-        String::from("")
-    };
-
-    let pass = if matches.is_present(PASS_FLAG) {
-        let pass_str = matches
-            .value_of(PASS_FLAG)
-            .expect(validate_flag_error!(PASS_FLAG));
-        let pass_str: String = pass_str.parse().unwrap();
-        Some(pass_str)
-    } else {
-        None
-    };
+    let exact = matches.is_present(EXACT_FLAG);
 
     let body = if matches.is_present(BODY_FLAG) {
         let body_str = matches
             .value_of(BODY_FLAG)
-            .expect(validate_flag_error!(BODY_FLAG));
+            .unwrap_or_else(validate_flag_error!(BODY_FLAG));
         let body_str: String = body_str.parse().unwrap();
-        body_str
+        Body::new(body_str.into_bytes())
     } else if matches.is_present(BODY_FILE_FLAG) {
         let filename = matches
             .value_of(BODY_FILE_FLAG)
-            .expect(validate_flag_error!(BODY_FILE_FLAG));
-        let body_contents = fs::read_to_string(filename).unwrap();
-        body_contents
+            .unwrap_or_else(validate_flag_error!(BODY_FILE_FLAG));
+        if matches.is_present(STREAM_BODY_FLAG) {
+            Body::stream(filename)
+        } else {
+            Body::new(
+                fs::read(filename).unwrap_or_else(|e| panic!("Unable to read {}: {}", filename, e)),
+            )
+        }
+    } else if let Some(fields) = matches.values_of(FORM_FLAG) {
+        Body::multipart(&fields.collect::<Vec<_>>())
+    } else if let Some(values) = matches.values_of(DATA_URLENCODE_FLAG) {
+        Body::urlencoded(&values.collect::<Vec<_>>())
     } else {
-        String::from("")
+        Body::default()
+    };
+
+    let body = match body_encoding(matches).unwrap_or_else(|e| fail(e)) {
+        Some(encoding) => body.encode(encoding),
+        None => body,
     };
 
-    let verbose = if matches.is_present(VERBOSE_FLAG) {
-        true
+    let compressed = matches.is_present(COMPRESSED_FLAG);
+
+    let verbose = matches.is_present(VERBOSE_FLAG);
+
+    let version = http_version(matches);
+
+    let max_concurrent_streams = max_streams(matches).unwrap_or_else(|e| fail(e));
+
+    let ca_cert = matches
+        .value_of(CACERT_FLAG)
+        .map(|path| tls::load_ca_cert(path).unwrap_or_else(|e| fail(e)));
+
+    let identity = if matches.is_present(CERT_FLAG) {
+        let cert = matches
+            .value_of(CERT_FLAG)
+            .unwrap_or_else(validate_flag_error!(CERT_FLAG));
+        let cert_type = cert_type(matches).unwrap_or_else(|e| fail(e));
+        let key = matches.value_of(KEY_FLAG);
+        let password = matches.value_of(CERT_PASS_FLAG).unwrap_or("");
+        Some(tls::load_identity(cert_type, cert, key, password).unwrap_or_else(|e| fail(e)))
     } else {
-        false
+        None
+    };
+
+    let tls = TlsOptions {
+        ca_cert,
+        identity,
+        insecure: matches.is_present(INSECURE_FLAG),
+        min_version: matches
+            .value_of(TLS_MIN_VERSION_FLAG)
+            .map(|version| tls::parse_version(version).unwrap_or_else(|e| fail(e))),
     };
 
-    let http_method: Method;
+    let resolve = http::resolve_overrides(
+        matches.values_of(RESOLVE_FLAG).into_iter().flatten(),
+        &hosts,
+    )
+    .unwrap_or_else(|e| fail(e));
 
-    match method {
-        "get" => {
-            http_method = Method::GET;
-        }
-        "post" => {
-            http_method = Method::POST;
-        }
-        "put" => {
-            http_method = Method::PUT;
-        }
-        "patch" => {
-            http_method = Method::PATCH;
-        }
-        _ => panic!(
-            "{} is not a supported HTTP method. Use one of: 'get', 'post', 'put', or 'patch'.",
-            method
-        ),
+    let max_redirects = matches
+        .value_of(MAX_REDIRECTS_FLAG)
+        .unwrap_or_else(validate_flag_error!(MAX_REDIRECTS_FLAG));
+    let max_redirects: usize =
+        number(MAX_REDIRECTS_FLAG, max_redirects).unwrap_or_else(|e| fail(e));
+
+    let http = HttpOptions {
+        version,
+        max_concurrent_streams,
+        tls,
+        resolve,
+        proxy: ProxyOptions::new(
+            matches.value_of(PROXY_FLAG),
+            matches.value_of(NO_PROXY_FLAG),
+        )
+        .unwrap_or_else(|e| fail(e)),
+        max_redirects,
+    };
+
+    let bearer = secret_value(matches, BEARER_FLAG, BEARER_ENV_FLAG, BEARER_FILE_FLAG)
+        .unwrap_or_else(|e| fail(e));
+    let api_key = secret_value(matches, API_KEY_FLAG, API_KEY_ENV_FLAG, API_KEY_FILE_FLAG)
+        .unwrap_or_else(|e| fail(e));
+
+    let auth = if matches.is_present(USER_FLAG) {
+        let user = matches
+            .value_of(USER_FLAG)
+            .unwrap_or_else(validate_flag_error!(USER_FLAG));
+        let pass = secret_value(matches, PASS_FLAG, PASS_ENV_FLAG, PASS_FILE_FLAG)
+            .unwrap_or_else(|e| fail(e))
+            .or_else(|| Secret::prompt("Password: "));
+        Some(Auth::Basic {
+            user: user.to_string(),
+            pass,
+        })
+    } else if let Some(token) = bearer {
+        Some(Auth::Bearer(token))
+    } else if let Some(key) = api_key {
+        let header = api_key_header(matches).unwrap_or_else(|e| fail(e));
+        Some(Auth::ApiKey { header, key })
+    } else if matches.is_present(OAUTH2_TOKEN_URL_FLAG) {
+        let token_url = matches
+            .value_of(OAUTH2_TOKEN_URL_FLAG)
+            .unwrap_or_else(validate_flag_error!(OAUTH2_TOKEN_URL_FLAG));
+        let client_id = matches
+            .value_of(OAUTH2_CLIENT_ID_FLAG)
+            .unwrap_or_else(validate_flag_error!(OAUTH2_CLIENT_ID_FLAG));
+        let client_secret = secret_value(
+            matches,
+            OAUTH2_CLIENT_SECRET_FLAG,
+            OAUTH2_CLIENT_SECRET_ENV_FLAG,
+            OAUTH2_CLIENT_SECRET_FILE_FLAG,
+        )
+        .unwrap_or_else(|e| fail(e))
+        .or_else(|| Secret::prompt("OAuth2 client secret: "))
+        .unwrap_or_else(validate_flag_error!(OAUTH2_CLIENT_SECRET_FLAG));
+        let scope = matches.value_of(OAUTH2_SCOPE_FLAG).map(String::from);
+        Some(Auth::OAuth2(OAuth2::new(
+            http.build_client(timeout),
+            token_url.to_string(),
+            client_id.to_string(),
+            client_secret,
+            scope,
+        )))
+    } else {
+        None
+    };
+
+    let sampler = matches.value_of(SAVE_RESPONSES_FLAG).map(|dir| {
+        let limit: usize = number(
+            SAVE_SAMPLE_FLAG,
+            matches.value_of(SAVE_SAMPLE_FLAG).unwrap_or("10"),
+        )
+        .unwrap_or_else(|e| fail(e));
+        Sampler::new(PathBuf::from(dir), limit)
+    });
+
+    let endpoint = matches
+        .value_of(OTLP_ENDPOINT_FLAG)
+        .map(|endpoint| trace::traces_url(endpoint).unwrap_or_else(|e| fail(e)));
+    let tracer = if matches.is_present(TRACEPARENT_FLAG) || endpoint.is_some() {
+        Some(Tracer::new(endpoint))
+    } else {
+        None
     };
 
     Client::new(
@@ -271,13 +1445,140 @@ pub fn burst_app() -> Client {
         duration,
         interval,
         exact,
-        host,
+        Targets::new(hosts, strategy),
         workers,
         timeout,
+        http,
         http_method,
         body,
-        user,
-        pass,
+        compressed,
+        auth,
+        sampler,
+        tracer,
+        outputs(matches),
+        scale(matches).unwrap_or_else(|e| fail(e)),
         verbose,
     )
 }
+
+// Checks a flag that takes a number was given one. clap only checks there
+// is a value.
+fn number<T: FromStr>(flag: &str, value: &str) -> Result<T, String> {
+    value
+        .parse()
+        .map_err(|_| format!("{} is not a valid number for --{}.", value, flag))
+}
+
+fn method(method: &str) -> Result<Method, String> {
+    match method {
+        "get" => Ok(Method::GET),
+        "post" => Ok(Method::POST),
+        "put" => Ok(Method::PUT),
+        "patch" => Ok(Method::PATCH),
+        _ => Err(format!(
+            "{} is not a supported HTTP method. Use one of: 'get', 'post', 'put', or 'patch'.",
+            method
+        )),
+    }
+}
+
+fn strategy(strategy: &str) -> Result<Strategy, String> {
+    match strategy {
+        "round-robin" => Ok(Strategy::RoundRobin),
+        "random" => Ok(Strategy::Random),
+        "least-in-flight" => Ok(Strategy::LeastInFlight),
+        _ => Err(format!(
+            "{} is not a supported strategy. Use one of: 'round-robin', 'random', or 'least-in-flight'.",
+            strategy
+        )),
+    }
+}
+
+// The hosts given with --host and --hosts-file, which all have to be http
+// or https URLs.
+fn hosts(matches: &ArgMatches) -> Result<Vec<String>, String> {
+    let mut hosts: Vec<String> = matches
+        .values_of(HOST_FLAG)
+        .map(|hosts| hosts.map(String::from).collect())
+        .unwrap_or_default();
+    if let Some(path) = matches.value_of(HOSTS_FILE_FLAG) {
+        hosts.extend(targets::read_hosts_file(path)?);
+    }
+    if hosts.is_empty() {
+        return Err(String::from("No hosts to send the requests to."));
+    }
+
+    for host in &hosts {
+        let url = Url::parse(host).map_err(|e| format!("{} is not a valid URL: {}", host, e))?;
+        if !matches!(url.scheme(), "http" | "https") {
+            return Err(format!("{} is not an http or https URL.", host));
+        }
+    }
+    Ok(hosts)
+}
+
+fn http_version(matches: &ArgMatches) -> HttpVersion {
+    if matches.is_present(HTTP2_PRIOR_KNOWLEDGE_FLAG) {
+        HttpVersion::Http2PriorKnowledge
+    } else if matches.is_present(HTTP2_FLAG) {
+        HttpVersion::Http2
+    } else {
+        HttpVersion::Http1
+    }
+}
+
+fn max_streams(matches: &ArgMatches) -> Result<Option<usize>, String> {
+    let streams = match matches.value_of(MAX_STREAMS_FLAG) {
+        Some(streams) => streams,
+        None => return Ok(None),
+    };
+    if http_version(matches) == HttpVersion::Http1 {
+        return Err(format!(
+            "--{} requires --{} or --{} to be set.",
+            MAX_STREAMS_FLAG, HTTP2_FLAG, HTTP2_PRIOR_KNOWLEDGE_FLAG
+        ));
+    }
+    match number(MAX_STREAMS_FLAG, streams)? {
+        0 => Err(format!("--{} must be greater than 0.", MAX_STREAMS_FLAG)),
+        streams => Ok(Some(streams)),
+    }
+}
+
+fn body_encoding(matches: &ArgMatches) -> Result<Option<Encoding>, String> {
+    matches
+        .value_of(BODY_ENCODING_FLAG)
+        .map(|encoding| {
+            Encoding::parse(encoding).ok_or_else(|| {
+                format!(
+                    "{} is not a supported body encoding. Use one of: 'gzip', 'deflate', 'br', or 'zstd'.",
+                    encoding
+                )
+            })
+        })
+        .transpose()
+}
+
+fn cert_type(matches: &ArgMatches) -> Result<CertType, String> {
+    match matches.value_of(CERT_TYPE_FLAG).unwrap_or("pem") {
+        "pem" => Ok(CertType::Pem),
+        "p12" => Ok(CertType::P12),
+        other => Err(format!(
+            "{} is not a supported certificate type. Use one of: 'pem' or 'p12'.",
+            other
+        )),
+    }
+}
+
+// Checks the file at `path` can be read, without reading it.
+fn readable(path: &str) -> Result<(), String> {
+    fs::File::open(path)
+        .map(drop)
+        .map_err(|e| format!("Unable to read {}: {}", path, e))
+}
+
+fn api_key_header(matches: &ArgMatches) -> Result<HeaderName, String> {
+    let header = matches.value_of(API_KEY_HEADER_FLAG).unwrap_or("X-API-Key");
+    header
+        .parse()
+        .map_err(|_| format!("{} is not a valid header name.", header))
+}
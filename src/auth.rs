@@ -0,0 +1,165 @@
+use reqwest::header::HeaderName;
+use reqwest::{RequestBuilder, Result};
+use serde::Deserialize;
+use tokio::sync::RwLock;
+
+use std::fmt;
+use std::time::{Duration, Instant};
+
+use crate::secret::Secret;
+
+/// Authentication scheme added to every request. Requests are sent
+/// without any credentials when the client has none set.
+#[derive(Debug)]
+pub enum Auth {
+    Basic { user: String, pass: Option<Secret> },
+    Bearer(Secret),
+    ApiKey { header: HeaderName, key: Secret },
+    OAuth2(OAuth2),
+}
+
+impl Auth {
+    /// Gets the scheme ready before any load is sent. For OAuth2 this
+    /// fetches the first token, so a misconfigured token endpoint fails
+    /// the run straight away instead of failing every request.
+    pub async fn prepare(&self) -> Result<()> {
+        if let Auth::OAuth2(oauth2) = self {
+            oauth2.access_token().await?;
+        }
+        Ok(())
+    }
+
+    pub async fn apply(&self, req: RequestBuilder) -> Result<RequestBuilder> {
+        let req = match self {
+            Auth::Basic { user, pass } => req.basic_auth(user, pass.as_ref().map(Secret::expose)),
+            Auth::Bearer(token) => req.bearer_auth(token.expose()),
+            Auth::ApiKey { header, key } => req.header(header, key.expose()),
+            Auth::OAuth2(oauth2) => req.bearer_auth(oauth2.access_token().await?.expose()),
+        };
+        Ok(req)
+    }
+}
+
+impl fmt::Display for Auth {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Auth::Basic {
+                user,
+                pass: Some(pass),
+            } => write!(f, "basic (user: {}, pass: {})", user, pass),
+            Auth::Basic { user, pass: None } => write!(f, "basic (user: {})", user),
+            Auth::Bearer(token) => write!(f, "bearer (token: {})", token),
+            Auth::ApiKey { header, key } => write!(f, "API key ({}: {})", header, key),
+            Auth::OAuth2(oauth2) => write!(
+                f,
+                "OAuth2 client credentials (token URL: {}, client ID: {}, client secret: {})",
+                oauth2.token_url, oauth2.client_id, oauth2.client_secret
+            ),
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct TokenResponse {
+    access_token: String,
+    expires_in: Option<u64>,
+}
+
+#[derive(Debug)]
+struct Token {
+    access_token: Secret,
+    expires_at: Option<Instant>,
+}
+
+impl Token {
+    fn expired(&self) -> bool {
+        match self.expires_at {
+            Some(expires_at) => Instant::now() >= expires_at,
+            None => false,
+        }
+    }
+}
+
+/// OAuth2 client credentials grant. The token is shared by every request
+/// and fetched again once it expires mid-run.
+#[derive(Debug)]
+pub struct OAuth2 {
+    req_client: reqwest::Client,
+    token_url: String,
+    client_id: String,
+    client_secret: Secret,
+    scope: Option<String>,
+    token: RwLock<Option<Token>>,
+}
+
+impl OAuth2 {
+    pub fn new(
+        req_client: reqwest::Client,
+        token_url: String,
+        client_id: String,
+        client_secret: Secret,
+        scope: Option<String>,
+    ) -> Self {
+        Self {
+            req_client,
+            token_url,
+            client_id,
+            client_secret,
+            scope,
+            token: RwLock::new(None),
+        }
+    }
+
+    async fn access_token(&self) -> Result<Secret> {
+        if let Some(token) = self.token.read().await.as_ref() {
+            if !token.expired() {
+                return Ok(token.access_token.clone());
+            }
+        }
+
+        let mut token = self.token.write().await;
+        // Another request may have refreshed the token while this one was
+        // waiting for the lock.
+        if let Some(token) = token.as_ref() {
+            if !token.expired() {
+                return Ok(token.access_token.clone());
+            }
+        }
+
+        let fresh = self.fetch().await?;
+        let access_token = fresh.access_token.clone();
+        *token = Some(fresh);
+        Ok(access_token)
+    }
+
+    async fn fetch(&self) -> Result<Token> {
+        let mut form = vec![("grant_type", "client_credentials")];
+        if let Some(scope) = &self.scope {
+            form.push(("scope", scope));
+        }
+
+        let requested_at = Instant::now();
+        let res: TokenResponse = self
+            .req_client
+            .post(&self.token_url)
+            .basic_auth(&self.client_id, Some(self.client_secret.expose()))
+            .form(&form)
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?;
+
+        // Refresh a little before the token actually expires so requests
+        // in flight don't get rejected.
+        let expires_at = res.expires_in.map(|secs| {
+            let lifetime = Duration::from_secs(secs);
+            requested_at + lifetime - lifetime / 10
+        });
+
+        Ok(Token {
+            access_token: Secret::new(res.access_token),
+            expires_at,
+        })
+    }
+}
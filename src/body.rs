@@ -0,0 +1,218 @@
+use bytes::Bytes;
+use futures::{stream, TryStreamExt};
+use rand::distributions::Alphanumeric;
+use rand::Rng;
+use reqwest::header::{CONTENT_ENCODING, CONTENT_LENGTH, CONTENT_TYPE};
+use reqwest::RequestBuilder;
+use tokio_util::io::ReaderStream;
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::compression::Encoding;
+
+/// Request body sent with every POST, PUT and PATCH request.
+///
+/// The body is the same for every request, so it's built, and compressed,
+/// once up front rather than for every request.
+#[derive(Clone, Debug, Default)]
+pub struct Body {
+    data: Data,
+    content_type: Option<String>,
+    encoding: Option<Encoding>,
+}
+
+#[derive(Clone, Debug)]
+enum Data {
+    /// Held in memory and shared by every request.
+    Bytes(Bytes),
+    /// Read from disk in chunks by every request, for bodies too large to
+    /// keep in memory.
+    File { path: PathBuf, len: u64 },
+}
+
+impl Default for Data {
+    fn default() -> Self {
+        Data::Bytes(Bytes::new())
+    }
+}
+
+impl Body {
+    pub fn new(data: Vec<u8>) -> Self {
+        Self {
+            data: Data::Bytes(Bytes::from(data)),
+            ..Default::default()
+        }
+    }
+
+    /// Streams the file at `path` as the body of every request.
+    pub fn stream(path: &str) -> Self {
+        let len = fs::metadata(path)
+            .unwrap_or_else(|e| panic!("Unable to read {}: {}", path, e))
+            .len();
+        Self {
+            data: Data::File {
+                path: PathBuf::from(path),
+                len,
+            },
+            ..Default::default()
+        }
+    }
+
+    /// Builds a multipart/form-data body from `key=value` fields, where
+    /// `key=@path` uploads the file at `path`.
+    pub fn multipart(fields: &[&str]) -> Self {
+        let boundary: String = rand::thread_rng()
+            .sample_iter(&Alphanumeric)
+            .take(24)
+            .map(char::from)
+            .collect();
+        let boundary = format!("burst-{}", boundary);
+
+        let mut data = Vec::new();
+        for field in fields {
+            let (name, value) = split_field(field).unwrap_or_else(|e| panic!("{}", e));
+            data.extend_from_slice(format!("--{}\r\n", boundary).as_bytes());
+            match value.strip_prefix('@') {
+                Some(path) => {
+                    let filename = Path::new(path)
+                        .file_name()
+                        .map(|name| name.to_string_lossy().into_owned())
+                        .unwrap_or_else(|| path.to_string());
+                    data.extend_from_slice(
+                        format!(
+                            "Content-Disposition: form-data; name=\"{}\"; filename=\"{}\"\r\n\
+                             Content-Type: application/octet-stream\r\n\r\n",
+                            quote(name),
+                            quote(&filename)
+                        )
+                        .as_bytes(),
+                    );
+                    data.extend(read_file(path));
+                }
+                None => {
+                    data.extend_from_slice(
+                        format!(
+                            "Content-Disposition: form-data; name=\"{}\"\r\n\r\n",
+                            quote(name)
+                        )
+                        .as_bytes(),
+                    );
+                    data.extend_from_slice(value.as_bytes());
+                }
+            }
+            data.extend_from_slice(b"\r\n");
+        }
+        data.extend_from_slice(format!("--{}--\r\n", boundary).as_bytes());
+
+        Self {
+            data: Data::Bytes(Bytes::from(data)),
+            content_type: Some(format!("multipart/form-data; boundary={}", boundary)),
+            encoding: None,
+        }
+    }
+
+    /// Builds an application/x-www-form-urlencoded body the way curl's
+    /// `--data-urlencode` does. Each value is one of `content`,
+    /// `name=content`, `@path` or `name@path`, and only the content is
+    /// encoded.
+    pub fn urlencoded(values: &[&str]) -> Self {
+        let pairs: Vec<String> = values
+            .iter()
+            .map(|value| {
+                let (name, content) = match value.find(['=', '@']) {
+                    Some(i) if value[i..].starts_with('@') => {
+                        (&value[..i], read_file(&value[i + 1..]))
+                    }
+                    Some(i) => (&value[..i], value.as_bytes()[i + 1..].to_vec()),
+                    None => ("", value.as_bytes().to_vec()),
+                };
+                let content: String = form_urlencoded::byte_serialize(&content).collect();
+                if name.is_empty() {
+                    content
+                } else {
+                    format!("{}={}", name, content)
+                }
+            })
+            .collect();
+
+        Self {
+            data: Data::Bytes(Bytes::from(pairs.join("&"))),
+            content_type: Some(String::from("application/x-www-form-urlencoded")),
+            encoding: None,
+        }
+    }
+
+    /// Compresses the body with `encoding`. Streamed bodies can't be
+    /// compressed.
+    pub fn encode(self, encoding: Encoding) -> Self {
+        let data = match &self.data {
+            Data::Bytes(data) => encoding
+                .encode(data)
+                .unwrap_or_else(|e| panic!("Unable to compress the request body: {}", e)),
+            Data::File { .. } => panic!("A streamed request body can't be compressed."),
+        };
+        Self {
+            data: Data::Bytes(Bytes::from(data)),
+            encoding: Some(encoding),
+            ..self
+        }
+    }
+
+    pub fn apply(&self, req: RequestBuilder) -> RequestBuilder {
+        let req = match &self.data {
+            // Cloning Bytes only bumps a reference count.
+            Data::Bytes(data) => req.body(data.clone()),
+            Data::File { path, len } => {
+                // The file is opened once the body is polled, so a file that
+                // can't be read shows up as a body error for that request.
+                let path = path.clone();
+                let body = stream::once(async move { tokio::fs::File::open(path).await })
+                    .map_ok(ReaderStream::new)
+                    .try_flatten();
+                // Sending the length up front avoids chunked encoding.
+                req.header(CONTENT_LENGTH, *len)
+                    .body(reqwest::Body::wrap_stream(body))
+            }
+        };
+        let req = match &self.content_type {
+            Some(content_type) => req.header(CONTENT_TYPE, content_type),
+            None => req,
+        };
+        match self.encoding {
+            Some(encoding) => req.header(CONTENT_ENCODING, encoding.name()),
+            None => req,
+        }
+    }
+}
+
+/// The file a `multipart` field uploads, if any.
+pub fn multipart_file(field: &str) -> Option<&str> {
+    field.split_once('=')?.1.strip_prefix('@')
+}
+
+/// The file a `urlencoded` value reads its content from, if any.
+pub fn urlencoded_file(value: &str) -> Option<&str> {
+    match value.find(['=', '@']) {
+        Some(i) if value[i..].starts_with('@') => Some(&value[i + 1..]),
+        _ => None,
+    }
+}
+
+/// Splits a `multipart` field into its name and value.
+pub fn split_field(field: &str) -> Result<(&str, &str), String> {
+    field.split_once('=').ok_or_else(|| {
+        format!(
+            "{} is not a valid form field. Use key=value or key=@path.",
+            field
+        )
+    })
+}
+
+fn quote(s: &str) -> String {
+    s.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+fn read_file(path: &str) -> Vec<u8> {
+    fs::read(path).unwrap_or_else(|e| panic!("Unable to read {}: {}", path, e))
+}
@@ -1,13 +1,26 @@
 use futures::{stream, StreamExt};
 use rand::Rng;
-use reqwest::{Method, Result};
-use usdt::dtrace_provider;
-
-use std::arch::asm;
-use std::time::{Duration, Instant};
-use std::{process, thread, time};
-
-dtrace_provider!("src/burst.d");
+use reqwest::header::{ACCEPT_ENCODING, CONTENT_ENCODING};
+use reqwest::{Method, Request, RequestBuilder, StatusCode, Url, Version};
+use tokio_util::task::AbortOnDropHandle;
+
+use std::sync::{Arc, Mutex};
+use std::time::{Instant, SystemTime};
+use std::{process, time};
+
+use crate::auth::Auth;
+use crate::body::Body;
+use crate::compression::{self, Decoder, Encoding, Sink};
+use crate::error::{Error, Result};
+use crate::histogram::Scale;
+use crate::http::{Connections, HttpOptions};
+use crate::monitor::Monitor;
+use crate::probes;
+use crate::redirect;
+use crate::report::{ErrorClass, Outputs, Record, Report};
+use crate::sampler::Sampler;
+use crate::targets::Targets;
+use crate::trace::{self, Span, Tracer};
 
 #[derive(Clone, Debug)]
 pub enum Kind {
@@ -16,71 +29,129 @@ pub enum Kind {
     TimedExact,
 }
 
-#[derive(Clone, Debug)]
+// What's kept of a response once its body has been read.
+struct Exchange {
+    status: StatusCode,
+    version: Version,
+    url: Url,
+    redirects: usize,
+    bytes: u64,
+    decoded_bytes: u64,
+}
+
+/// Everything needed to send load. It never changes once the run starts,
+/// so a single `Client` is shared by every request behind an `Arc`.
+#[derive(Debug)]
 pub struct Client {
-    pub req_client: reqwest::Client,
-    pub requests: Vec<usize>,
+    pub connections: Connections,
+    pub max_redirects: usize,
+    pub requests: usize,
     pub duration: u64,
     pub interval: u64,
-    pub host: String,
+    pub targets: Arc<Targets>,
     pub workers: usize,
     pub method: Method,
-    pub body: String,
-    pub user: String,
-    pub pass: Option<String>,
+    pub body: Body,
+    pub compressed: bool,
+    pub auth: Option<Arc<Auth>>,
+    pub sampler: Option<Arc<Sampler>>,
+    pub tracer: Option<Arc<Tracer>>,
+    pub outputs: Outputs,
     pub verbose: bool,
     pub kind: Kind,
+    pub report: Arc<Mutex<Report>>,
+    pub monitor: Arc<Monitor>,
 }
 
 impl Client {
+    #[allow(clippy::too_many_arguments)]
     pub fn new(
-        requests: Vec<usize>,
+        requests: usize,
         duration: u64,
         interval: u64,
         exact: bool,
-        host: String,
+        targets: Targets,
         workers: usize,
         timeout: u64,
+        http: HttpOptions,
         method: Method,
-        body: String,
-        user: String,
-        pass: Option<String>,
+        body: Body,
+        compressed: bool,
+        auth: Option<Auth>,
+        sampler: Option<Sampler>,
+        tracer: Option<Tracer>,
+        outputs: Outputs,
+        scale: Scale,
         verbose: bool,
     ) -> Self {
-        let user_agent: &str = concat!(env!("CARGO_PKG_NAME"), "/", env!("CARGO_PKG_VERSION"));
-        let req_client = reqwest::Client::builder()
-            .timeout(Duration::from_secs(timeout))
-            .user_agent(user_agent)
-            .build()
-            .expect("Unable to build client");
+        let connections = Connections::new(&http, timeout, workers);
+        let max_redirects = http.max_redirects;
 
         let kind = kind_match(&duration, &exact);
+        let mut report = Report::new(targets.hosts().to_vec());
+        report.set_scale(scale);
 
         Self {
-            req_client,
+            connections,
+            max_redirects,
             requests,
             duration,
             interval,
-            host,
+            report: Arc::new(Mutex::new(report)),
+            targets: Arc::new(targets),
             workers,
             method,
             body,
-            user,
-            pass,
+            compressed,
+            auth: auth.map(Arc::new),
+            sampler: sampler.map(Arc::new),
+            tracer: tracer.map(Arc::new),
+            outputs,
             verbose,
             kind,
+            monitor: Arc::new(Monitor::new()),
         }
     }
 
     // I'm not sure if these methods that execute other methods should be
     // standalone functions that take Client as a parameter instead.
     // Which would be idiomatic Rust?
-    pub async fn send_load(&self) {
+    pub async fn send_load(self: &Arc<Self>) {
+        match self.run().await {
+            Ok(report) => {
+                println!("{}", report);
+                if let Err(e) = self.outputs.save(&report) {
+                    eprintln!("Unable to save the results: {}", e);
+                    process::exit(1);
+                }
+            }
+            Err(e) => {
+                eprintln!("Unable to authenticate: {}", e);
+                process::exit(1);
+            }
+        }
+    }
+
+    /// Sends the load and returns the report for the run. Fails when the
+    /// authentication scheme can't be set up.
+    pub async fn run(self: &Arc<Self>) -> reqwest::Result<Report> {
         let id: u64 = rand::thread_rng().gen();
+        self.report.lock().unwrap().start();
+        self.monitor.start();
+        if let Some(tracer) = &self.tracer {
+            tracer.start(id);
+        }
+
+        if let Some(auth) = &self.auth {
+            if self.verbose {
+                println!("Authentication: {}", auth);
+            }
+            auth.prepare().await?;
+        }
 
         match self.kind {
             Kind::Single => {
-                println!("Sending {} requests...", self.requests.len());
+                println!("Sending {} requests...", self.requests);
                 self.process_requests(id).await;
             }
             Kind::Timed => {
@@ -97,102 +168,250 @@ impl Client {
                 self.process_requests_timed_exact(id).await;
             }
         }
+
+        let report = {
+            let mut report = self.report.lock().unwrap();
+            report.finish(self.monitor.usage());
+            report.clone()
+        };
+
+        // Spans are exported in the background during the run. Failing to
+        // export them doesn't fail the run.
+        if let Some(tracer) = &self.tracer {
+            match tracer.finish().await {
+                Ok(export) => {
+                    if self.verbose {
+                        println!("Exported {} spans", export.exported);
+                    }
+                    if export.dropped > 0 {
+                        eprintln!(
+                            "Dropped {} spans, as the collector couldn't keep up",
+                            export.dropped
+                        );
+                    }
+                }
+                Err(e) => eprintln!("Unable to export spans: {}", e),
+            }
+        }
+
+        Ok(report)
     }
 
-    async fn get(&self) -> Result<()> {
-        let id: u64 = rand::thread_rng().gen();
-        burst::get__start!(|| id);
+    // Sends a request and records its outcome in the report.
+    async fn send(&self, id: u64, target: usize, req: RequestBuilder) -> Result<()> {
+        let host = self.targets.host(target);
+        probes::start(&self.method, id, host);
 
-        let res = self
-            .req_client
-            .get(&self.host)
-            .basic_auth(&self.user, self.pass.as_ref())
-            .send()
-            .await?;
-        burst::get__done!(|| id);
+        let req = if self.compressed {
+            req.header(ACCEPT_ENCODING, compression::ACCEPT_ENCODING)
+        } else {
+            req
+        };
+        let req = match &self.tracer {
+            Some(tracer) => req.header(trace::TRACEPARENT, tracer.traceparent(id)),
+            None => req,
+        };
+        let req = match &self.auth {
+            Some(auth) => auth.apply(req).await,
+            None => Ok(req),
+        };
+
+        let started = SystemTime::now();
+        let start = Instant::now();
+        let res = match req.map(RequestBuilder::build_split) {
+            Ok((req_client, Ok(req))) => self.execute(id, &req_client, req).await,
+            Ok((_, Err(e))) | Err(e) => Err(Error::from(e)),
+        };
+
+        let latency = start.elapsed();
+        let error = res
+            .as_ref()
+            .err()
+            .map(|e| ErrorClass::from_error(e, self.connections.is_proxied(host)));
+        match (&res, error) {
+            (Ok(ex), _) => probes::response(id, ex.status.as_u16(), latency, ex.url.as_str()),
+            (Err(_), Some(class)) => probes::error(id, latency, class, host),
+            _ => {}
+        }
+        let status = res.as_ref().map_or(0, |ex| ex.status.as_u16());
+        probes::done(&self.method, id, status, latency);
+        if let Some(tracer) = &self.tracer {
+            tracer.record(Span {
+                id,
+                method: &self.method,
+                url: host,
+                started,
+                latency,
+                status: res.as_ref().ok().map(|ex| ex.status.as_u16()),
+                error,
+            });
+        }
 
-        // TODO: Maybe create a summary of how many requests returned each status as well?
+        let mut report = self.report.lock().unwrap();
+        let record = Record {
+            offset: report.offset(start),
+            latency,
+            status: res.as_ref().ok().map(|ex| ex.status),
+            version: res.as_ref().ok().map(|ex| ex.version),
+            error,
+            target,
+            redirects: res.as_ref().map(|ex| ex.redirects).unwrap_or(0),
+            final_url: match &res {
+                Ok(ex) if ex.redirects > 0 => Some(ex.url.to_string()),
+                _ => None,
+            },
+            bytes: res.as_ref().map(|ex| ex.bytes).unwrap_or(0),
+            decoded_bytes: res.as_ref().map(|ex| ex.decoded_bytes).unwrap_or(0),
+        };
+        report.add(record);
+        drop(report);
+
+        let ex = res?;
         if self.verbose {
-            println!("Request ID: {} status: {}", id, res.status());
+            let redirected = if ex.redirects > 0 {
+                format!(" redirects: {} url: {}", ex.redirects, ex.url)
+            } else {
+                String::new()
+            };
+            let decoded = if ex.decoded_bytes != ex.bytes {
+                format!(" decoded: {}", ex.decoded_bytes)
+            } else {
+                String::new()
+            };
+            println!(
+                "Request ID: {} host: {} status: {} version: {:?} bytes: {}{}{}",
+                id, host, ex.status, ex.version, ex.bytes, decoded, redirected
+            );
         }
 
-        // The following prints the response body. Might be useful to have as an
-        // option to print it all out in a file?.
-        // let body = res.text().await?;
-        // println!("Body:\n\n{}", body);
         Ok(())
     }
 
-    async fn post(&self) -> Result<()> {
-        let id: u64 = rand::thread_rng().gen();
-        burst::post__start!(|| id);
-
-        let res = self
-            .req_client
-            .post(&self.host)
-            .basic_auth(&self.user, self.pass.as_ref())
-            .body(self.body.to_string())
-            .send()
-            .await?;
+    // Executes a request, following up to `max_redirects` redirects, and
+    // reads the final response body.
+    async fn execute(
+        &self,
+        id: u64,
+        req_client: &reqwest::Client,
+        req: Request,
+    ) -> Result<Exchange> {
+        let mut prev = req.try_clone();
+        let mut res = req_client.execute(req).await?;
+        if probes::ENABLED && self.connections.opened(&res) {
+            probes::connection(id, res.url().as_str());
+        }
 
-        burst::post__done!(|| id);
+        let mut redirects = 0;
+        while redirects < self.max_redirects {
+            let next = match prev
+                .as_ref()
+                .and_then(|prev| redirect::next_request(prev, &res))
+            {
+                Some(next) => next,
+                None => break,
+            };
+            // Drain the redirect so its connection can be reused.
+            while res.chunk().await?.is_some() {}
+
+            redirects += 1;
+            probes::redirect(id, redirects, next.url().as_str());
+            prev = next.try_clone();
+            res = req_client.execute(next).await?;
+            if probes::ENABLED && self.connections.opened(&res) {
+                probes::connection(id, res.url().as_str());
+            }
+        }
 
-        if self.verbose {
-            println!("Request ID: {} status: {}", id, res.status());
+        let status = res.status();
+        let version = res.version();
+        let url = res.url().clone();
+
+        // Bodies are always read in full, so transfer time is part of the
+        // request lifetime and connections go back to the pool cleanly.
+        let sink = Sink {
+            bytes: 0,
+            sample: match &self.sampler {
+                Some(sampler) if sampler.take() => Some(Vec::new()),
+                _ => None,
+            },
+        };
+        // Only bodies burst asked to be compressed are decoded, anything
+        // else is counted as is.
+        let encoding = if self.compressed {
+            res.headers()
+                .get(CONTENT_ENCODING)
+                .and_then(|encoding| encoding.to_str().ok())
+                .and_then(Encoding::parse)
+        } else {
+            None
+        };
+        let mut decoder = Decoder::new(encoding, sink).map_err(Error::Decode)?;
+        let mut bytes: u64 = 0;
+        while let Some(chunk) = res.chunk().await? {
+            bytes += chunk.len() as u64;
+            decoder.write(&chunk).map_err(Error::Decode)?;
+        }
+        let sink = decoder.finish().map_err(Error::Decode)?;
+        if let (Some(sampler), Some(sample)) = (&self.sampler, sink.sample) {
+            sampler.save(id, status.as_u16(), &sample).await;
         }
 
-        Ok(())
+        Ok(Exchange {
+            status,
+            version,
+            url,
+            redirects,
+            bytes,
+            decoded_bytes: sink.bytes,
+        })
     }
 
-    async fn put(&self) -> Result<()> {
+    async fn get(&self) -> Result<()> {
         let id: u64 = rand::thread_rng().gen();
-        burst::put__start!(|| id);
-
-        let res = self
-            .req_client
-            .put(&self.host)
-            .basic_auth(&self.user, self.pass.as_ref())
-            .body(self.body.to_string())
-            .send()
-            .await?;
+        let (req_client, _permit) = self.connections.acquire().await;
+        let (target, _in_flight) = self.targets.pick();
 
-        burst::put__done!(|| id);
+        let req = req_client.get(self.targets.host(target));
+        self.send(id, target, req).await
+    }
 
-        if self.verbose {
-            println!("Request ID: {} status: {}", id, res.status());
-        }
+    async fn post(&self) -> Result<()> {
+        let id: u64 = rand::thread_rng().gen();
+        let (req_client, _permit) = self.connections.acquire().await;
+        let (target, _in_flight) = self.targets.pick();
 
-        Ok(())
+        let req = self.body.apply(req_client.post(self.targets.host(target)));
+        self.send(id, target, req).await
     }
 
-    async fn patch(&self) -> Result<()> {
+    async fn put(&self) -> Result<()> {
         let id: u64 = rand::thread_rng().gen();
-        burst::patch__start!(|| id);
-
-        let res = self
-            .req_client
-            .patch(&self.host)
-            .basic_auth(&self.user, self.pass.as_ref())
-            .body(self.body.to_string())
-            .send()
-            .await?;
+        let (req_client, _permit) = self.connections.acquire().await;
+        let (target, _in_flight) = self.targets.pick();
 
-        burst::patch__done!(|| id);
+        let req = self.body.apply(req_client.put(self.targets.host(target)));
+        self.send(id, target, req).await
+    }
 
-        if self.verbose {
-            println!("Request ID: {} status: {}", id, res.status());
-        }
+    async fn patch(&self) -> Result<()> {
+        let id: u64 = rand::thread_rng().gen();
+        let (req_client, _permit) = self.connections.acquire().await;
+        let (target, _in_flight) = self.targets.pick();
 
-        Ok(())
+        let req = self.body.apply(req_client.patch(self.targets.host(target)));
+        self.send(id, target, req).await
     }
 
-    async fn process_requests(&self, id: u64) {
-        burst::requests__start!(|| id);
+    async fn process_requests(self: &Arc<Self>, id: u64) {
+        probes::requests_start(id);
 
-        let requests = stream::iter(&self.requests)
+        let requests = stream::iter(0..self.requests)
             .map(|_| {
-                let client = self.clone();
-                tokio::spawn(async move {
+                let client = Arc::clone(self);
+                let due = Instant::now();
+                // Aborted if the burst is cut short by --exact.
+                AbortOnDropHandle::new(tokio::spawn(async move {
+                    client.monitor.record_lag(due.elapsed());
                     match client.method {
                         Method::GET => {
                             if let Err(e) = client.get().await {
@@ -216,7 +435,7 @@ impl Client {
                         }
                         _ => eprintln!("{} is not a supported HTTP method", client.method),
                     }
-                })
+                }))
             })
             .buffer_unordered(self.workers);
 
@@ -228,11 +447,12 @@ impl Client {
             })
             .await;
 
-        burst::requests__done!(|| id);
+        probes::requests_done(id);
     }
 
-    async fn process_requests_timed(&self, id: u64) {
+    async fn process_requests_timed(self: &Arc<Self>, id: u64) {
         let now = Instant::now();
+        let mut batch = 0;
 
         if self.interval > 0 {
             let mut interval = tokio::time::interval(time::Duration::from_secs(self.interval));
@@ -242,37 +462,46 @@ impl Client {
                     println!("Pausing for {} seconds", self.interval);
                 }
                 interval.tick().await;
+                probes::tick(id, batch, now.elapsed());
+                batch += 1;
                 self.process_requests(id).await;
             }
         } else {
             while now.elapsed().as_secs() < self.duration {
+                probes::tick(id, batch, now.elapsed());
+                batch += 1;
                 self.process_requests(id).await;
             }
         }
     }
 
-    async fn process_requests_timed_exact(&self, id: u64) {
-        let duration = self.duration;
-        thread::spawn(move || {
-            thread::sleep(time::Duration::from_secs(duration));
-            process::exit(0);
-        });
-
-        if self.interval > 0 {
-            let mut interval = tokio::time::interval(time::Duration::from_secs(self.interval));
-
-            loop {
-                if self.verbose {
-                    println!("Pausing for {} seconds", self.interval);
+    async fn process_requests_timed_exact(self: &Arc<Self>, id: u64) {
+        // Requests still in flight when the time is up are dropped.
+        let duration = time::Duration::from_secs(self.duration);
+        let now = Instant::now();
+        let mut batch = 0;
+        let _ = tokio::time::timeout(duration, async {
+            if self.interval > 0 {
+                let mut interval = tokio::time::interval(time::Duration::from_secs(self.interval));
+
+                loop {
+                    if self.verbose {
+                        println!("Pausing for {} seconds", self.interval);
+                    }
+                    interval.tick().await;
+                    probes::tick(id, batch, now.elapsed());
+                    batch += 1;
+                    self.process_requests(id).await;
+                }
+            } else {
+                loop {
+                    probes::tick(id, batch, now.elapsed());
+                    batch += 1;
+                    self.process_requests(id).await;
                 }
-                interval.tick().await;
-                self.process_requests(id).await;
-            }
-        } else {
-            loop {
-                self.process_requests(id).await;
             }
-        }
+        })
+        .await;
     }
 }
 
@@ -0,0 +1,242 @@
+use std::collections::BTreeMap;
+use std::fmt;
+use std::time::Duration;
+
+use crate::histogram::Latencies;
+use crate::report::{self, Report};
+
+const PERCENTILES: [f64; 5] = [50.0, 90.0, 95.0, 99.0, 99.9];
+
+/// How the candidate run did against the baseline.
+#[derive(Debug)]
+pub struct Comparison {
+    deltas: Vec<Delta>,
+    /// Probability the latencies of both runs come from the same
+    /// distribution, from a Mann-Whitney U test.
+    latency_p: f64,
+    tolerance: f64,
+}
+
+// How a single metric moved between the two runs.
+#[derive(Debug)]
+struct Delta {
+    metric: String,
+    baseline: String,
+    candidate: String,
+    change: String,
+    /// How much worse the candidate did, in percent. Negative when it did
+    /// better.
+    worse_by: f64,
+    /// Whether the change is unlikely to be down to chance.
+    significant: bool,
+}
+
+impl Comparison {
+    /// Compares `candidate` to `baseline`. Changes smaller than
+    /// `tolerance` percent, or that aren't significant at `alpha`, aren't
+    /// counted as regressions. Error rates are compared in percentage
+    /// points.
+    pub fn new(baseline: &Report, candidate: &Report, tolerance: f64, alpha: f64) -> Self {
+        let mut deltas = Vec::new();
+
+        // There's a single throughput figure per run, so there's nothing to
+        // test it against. Any drop past the tolerance counts.
+        let (base_rps, cand_rps) = (rps(baseline), rps(candidate));
+        let change = relative(base_rps, cand_rps);
+        deltas.push(Delta {
+            metric: String::from("requests per second"),
+            baseline: format!("{:.3}", base_rps),
+            candidate: format!("{:.3}", cand_rps),
+            change: format!("{:+.1}%", change),
+            worse_by: -change,
+            significant: true,
+        });
+
+        let (base_errors, base_total) = errors(baseline);
+        let (cand_errors, cand_total) = errors(candidate);
+        let (base_rate, cand_rate) = (rate(base_errors, base_total), rate(cand_errors, cand_total));
+        deltas.push(Delta {
+            metric: String::from("error rate"),
+            baseline: format!("{:.2}%", base_rate),
+            candidate: format!("{:.2}%", cand_rate),
+            change: format!("{:+.2}pp", cand_rate - base_rate),
+            worse_by: cand_rate - base_rate,
+            significant: two_proportions(base_errors, base_total, cand_errors, cand_total) < alpha,
+        });
+
+        let (base_latencies, cand_latencies) =
+            (&baseline.total().latencies, &candidate.total().latencies);
+        let latency_p = mann_whitney(base_latencies, cand_latencies);
+        for p in PERCENTILES {
+            let base = base_latencies.percentile(p);
+            let cand = cand_latencies.percentile(p);
+            let change = relative(base.as_secs_f64(), cand.as_secs_f64());
+            deltas.push(Delta {
+                metric: format!("p{} latency", p),
+                baseline: report::millis(base),
+                candidate: report::millis(cand),
+                change: format!("{:+.1}%", change),
+                worse_by: change,
+                significant: latency_p < alpha,
+            });
+        }
+
+        Self {
+            deltas,
+            latency_p,
+            tolerance,
+        }
+    }
+
+    /// Whether the candidate did significantly worse than the tolerance
+    /// allows on any metric.
+    pub fn regressed(&self) -> bool {
+        self.deltas.iter().any(|d| self.is_regression(d))
+    }
+
+    fn is_regression(&self, delta: &Delta) -> bool {
+        delta.significant && delta.worse_by > self.tolerance
+    }
+}
+
+impl fmt::Display for Comparison {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "Comparison:")?;
+        writeln!(
+            f,
+            "  {:<24}{:>16}{:>16}{:>12}",
+            "metric", "baseline", "candidate", "change"
+        )?;
+        for delta in &self.deltas {
+            let verdict = if self.is_regression(delta) {
+                "  regression"
+            } else if !delta.significant {
+                "  not significant"
+            } else {
+                ""
+            };
+            writeln!(
+                f,
+                "  {:<24}{:>16}{:>16}{:>12}{}",
+                delta.metric, delta.baseline, delta.candidate, delta.change, verdict
+            )?;
+        }
+        writeln!(
+            f,
+            "Latency distributions differ with p = {:.4} (Mann-Whitney U).",
+            self.latency_p
+        )?;
+
+        if self.regressed() {
+            write!(f, "Regressed past the {}% tolerance.", self.tolerance)
+        } else {
+            write!(f, "No regressions past the {}% tolerance.", self.tolerance)
+        }
+    }
+}
+
+fn rps(report: &Report) -> f64 {
+    let responses = report.total().responses();
+    let elapsed = report.elapsed().as_secs_f64();
+    if elapsed == 0.0 {
+        0.0
+    } else {
+        responses as f64 / elapsed
+    }
+}
+
+fn errors(report: &Report) -> (u64, u64) {
+    (report.total().failures(), report.total().requests)
+}
+
+fn rate(errors: u64, total: u64) -> f64 {
+    if total == 0 {
+        0.0
+    } else {
+        errors as f64 / total as f64 * 100.0
+    }
+}
+
+// Change from `base` to `cand` in percent.
+fn relative(base: f64, cand: f64) -> f64 {
+    if base == 0.0 {
+        0.0
+    } else {
+        (cand - base) / base * 100.0
+    }
+}
+
+// Two-sided p-value of a two-proportion z-test.
+fn two_proportions(e1: u64, n1: u64, e2: u64, n2: u64) -> f64 {
+    if n1 == 0 || n2 == 0 {
+        return 1.0;
+    }
+    let (n1, n2) = (n1 as f64, n2 as f64);
+    let pooled = (e1 + e2) as f64 / (n1 + n2);
+    let se = (pooled * (1.0 - pooled) * (1.0 / n1 + 1.0 / n2)).sqrt();
+    if se == 0.0 {
+        return 1.0;
+    }
+    let z = (e2 as f64 / n2 - e1 as f64 / n1) / se;
+    erfc(z.abs() / std::f64::consts::SQRT_2)
+}
+
+// Two-sided p-value of a Mann-Whitney U test, using the normal
+// approximation with a correction for ties. Latencies in the same bucket
+// count as tied.
+fn mann_whitney(a: &Latencies, b: &Latencies) -> f64 {
+    if a.is_empty() || b.is_empty() {
+        return 1.0;
+    }
+
+    let mut buckets: BTreeMap<Duration, (u64, u64)> = BTreeMap::new();
+    for (latency, count) in a.buckets() {
+        buckets.entry(latency).or_default().0 += count;
+    }
+    for (latency, count) in b.buckets() {
+        buckets.entry(latency).or_default().1 += count;
+    }
+
+    // Tied values share the average of the ranks they span.
+    let mut rank_sum_a = 0.0;
+    let mut ties = 0.0;
+    let mut seen = 0.0;
+    for (in_a, in_b) in buckets.into_values() {
+        let t = (in_a + in_b) as f64;
+        let rank = seen + (t + 1.0) / 2.0;
+        rank_sum_a += rank * in_a as f64;
+        ties += t * t * t - t;
+        seen += t;
+    }
+
+    let (n1, n2) = (a.count() as f64, b.count() as f64);
+    let n = n1 + n2;
+    let u = rank_sum_a - n1 * (n1 + 1.0) / 2.0;
+    let mean = n1 * n2 / 2.0;
+    let sd = (n1 * n2 / 12.0 * ((n + 1.0) - ties / (n * (n - 1.0)))).sqrt();
+    if sd == 0.0 || sd.is_nan() {
+        return 1.0;
+    }
+    let z = (u - mean) / sd;
+    erfc(z.abs() / std::f64::consts::SQRT_2)
+}
+
+// Complementary error function, accurate to about 1e-7.
+fn erfc(x: f64) -> f64 {
+    let z = x.abs();
+    let t = 1.0 / (1.0 + 0.5 * z);
+    let poly = -z * z - 1.265_512_23
+        + t * (1.000_023_68
+            + t * (0.374_091_96
+                + t * (0.096_784_18
+                    + t * (-0.186_288_06
+                        + t * (0.278_868_07
+                            + t * (-1.135_203_98
+                                + t * (1.488_515_87 + t * (-0.822_152_23 + t * 0.170_872_77))))))));
+    let r = t * poly.exp();
+    if x >= 0.0 {
+        r
+    } else {
+        2.0 - r
+    }
+}
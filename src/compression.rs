@@ -0,0 +1,136 @@
+use flate2::write::{GzDecoder, GzEncoder, ZlibDecoder, ZlibEncoder};
+use flate2::Compression;
+
+use std::io::{self, Write};
+
+/// Value of the Accept-Encoding header sent with --compressed.
+pub const ACCEPT_ENCODING: &str = "gzip, deflate, br, zstd";
+
+/// HTTP content coding.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Encoding {
+    Gzip,
+    Deflate,
+    Brotli,
+    Zstd,
+}
+
+impl Encoding {
+    pub fn parse(encoding: &str) -> Option<Self> {
+        match encoding.trim().to_lowercase().as_str() {
+            "gzip" | "x-gzip" => Some(Encoding::Gzip),
+            "deflate" => Some(Encoding::Deflate),
+            "br" => Some(Encoding::Brotli),
+            "zstd" => Some(Encoding::Zstd),
+            _ => None,
+        }
+    }
+
+    /// Value of the Content-Encoding header for this coding.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Encoding::Gzip => "gzip",
+            Encoding::Deflate => "deflate",
+            Encoding::Brotli => "br",
+            Encoding::Zstd => "zstd",
+        }
+    }
+
+    pub fn encode(&self, data: &[u8]) -> io::Result<Vec<u8>> {
+        match self {
+            Encoding::Gzip => {
+                let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+                encoder.write_all(data)?;
+                encoder.finish()
+            }
+            Encoding::Deflate => {
+                let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
+                encoder.write_all(data)?;
+                encoder.finish()
+            }
+            Encoding::Brotli => {
+                let mut encoded = Vec::new();
+                {
+                    let mut encoder = brotli::CompressorWriter::new(&mut encoded, 4096, 5, 22);
+                    encoder.write_all(data)?;
+                }
+                Ok(encoded)
+            }
+            Encoding::Zstd => zstd::encode_all(data, 0),
+        }
+    }
+}
+
+/// Receives the decoded response body, counting it and keeping a copy
+/// when it's being sampled.
+#[derive(Debug, Default)]
+pub struct Sink {
+    pub bytes: u64,
+    pub sample: Option<Vec<u8>>,
+}
+
+impl Write for Sink {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.bytes += buf.len() as u64;
+        if let Some(sample) = self.sample.as_mut() {
+            sample.extend_from_slice(buf);
+        }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Decodes a response body chunk by chunk as it comes off the wire.
+pub enum Decoder {
+    Identity(Sink),
+    Gzip(GzDecoder<Sink>),
+    Deflate(ZlibDecoder<Sink>),
+    Brotli(Box<brotli::DecompressorWriter<Sink>>),
+    Zstd(zstd::stream::write::Decoder<'static, Sink>),
+}
+
+impl Decoder {
+    pub fn new(encoding: Option<Encoding>, sink: Sink) -> io::Result<Self> {
+        let decoder = match encoding {
+            None => Decoder::Identity(sink),
+            Some(Encoding::Gzip) => Decoder::Gzip(GzDecoder::new(sink)),
+            Some(Encoding::Deflate) => Decoder::Deflate(ZlibDecoder::new(sink)),
+            Some(Encoding::Brotli) => {
+                Decoder::Brotli(Box::new(brotli::DecompressorWriter::new(sink, 4096)))
+            }
+            Some(Encoding::Zstd) => Decoder::Zstd(zstd::stream::write::Decoder::new(sink)?),
+        };
+        Ok(decoder)
+    }
+
+    pub fn write(&mut self, chunk: &[u8]) -> io::Result<()> {
+        match self {
+            Decoder::Identity(sink) => sink.write_all(chunk),
+            Decoder::Gzip(decoder) => decoder.write_all(chunk),
+            Decoder::Deflate(decoder) => decoder.write_all(chunk),
+            Decoder::Brotli(decoder) => decoder.write_all(chunk),
+            Decoder::Zstd(decoder) => decoder.write_all(chunk),
+        }
+    }
+
+    pub fn finish(self) -> io::Result<Sink> {
+        match self {
+            Decoder::Identity(sink) => Ok(sink),
+            Decoder::Gzip(decoder) => decoder.finish(),
+            Decoder::Deflate(decoder) => decoder.finish(),
+            Decoder::Brotli(mut decoder) => {
+                decoder.close()?;
+                decoder.into_inner().map_err(|_| {
+                    io::Error::new(io::ErrorKind::InvalidData, "incomplete brotli stream")
+                })
+            }
+            Decoder::Zstd(mut decoder) => {
+                decoder.flush()?;
+                Ok(decoder.into_inner())
+            }
+        }
+    }
+}
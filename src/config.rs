@@ -0,0 +1,70 @@
+use toml::{Table, Value};
+
+use std::fs;
+
+const CONFIG_FLAG: &str = "--config";
+
+/// Reads the flags of a run from a TOML file. Each key is the long name of
+/// a flag: `true` sets a flag that takes no value, and an array repeats a
+/// flag once per value.
+///
+/// ```toml
+/// host = ["http://10.0.0.1", "http://10.0.0.2"]
+/// load = 1000
+/// http2 = true
+/// ```
+pub fn args(path: &str) -> Result<Vec<String>, String> {
+    let contents =
+        fs::read_to_string(path).map_err(|e| format!("Unable to read {}: {}", path, e))?;
+    let table: Table = contents
+        .parse()
+        .map_err(|e| format!("{} is not valid TOML: {}", path, e))?;
+
+    let mut args = Vec::new();
+    for (key, value) in table {
+        let flag = format!("--{}", key);
+        let values = match value {
+            Value::Array(values) => values,
+            value => vec![value],
+        };
+        for value in values {
+            match value {
+                Value::Boolean(true) => args.push(flag.clone()),
+                Value::Boolean(false) => {}
+                Value::String(value) => args.extend([flag.clone(), value]),
+                Value::Integer(value) => args.extend([flag.clone(), value.to_string()]),
+                Value::Float(value) => args.extend([flag.clone(), value.to_string()]),
+                value => {
+                    return Err(format!(
+                        "{} in {} is a {}, which can't be used as a flag.",
+                        key,
+                        path,
+                        value.type_str()
+                    ))
+                }
+            }
+        }
+    }
+
+    Ok(args)
+}
+
+/// Replaces every `--config <path>` in `args` with the flags read from
+/// `path`, so they're parsed and validated like any other flag.
+pub fn expand(args: Vec<String>) -> Result<Vec<String>, String> {
+    let mut expanded = Vec::with_capacity(args.len());
+    let mut args = args.into_iter();
+    while let Some(arg) = args.next() {
+        let path = if arg == CONFIG_FLAG {
+            args.next()
+                .ok_or_else(|| format!("{} requires a path to a file.", CONFIG_FLAG))?
+        } else if let Some(path) = arg.strip_prefix(&format!("{}=", CONFIG_FLAG)) {
+            path.to_string()
+        } else {
+            expanded.push(arg);
+            continue;
+        };
+        expanded.extend(self::args(&path)?);
+    }
+    Ok(expanded)
+}
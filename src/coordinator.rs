@@ -0,0 +1,193 @@
+use futures::future;
+use reqwest::{StatusCode, Url};
+
+use std::process;
+use std::time::Duration;
+
+use crate::agent::Job;
+use crate::histogram::Scale;
+use crate::report::{Outputs, Report};
+use crate::secret::Secret;
+
+// How long an agent has to answer a single request. Runs can take longer, so
+// the report is polled for rather than waited on.
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(30);
+// How often agents are asked whether their share of the run is over.
+const POLL_INTERVAL: Duration = Duration::from_millis(250);
+
+/// Spreads a run across several agents, starts them together and merges
+/// what they send back into one report.
+#[derive(Debug)]
+pub struct Coordinator {
+    agents: Vec<Url>,
+    args: Vec<String>,
+    load: usize,
+    workers: usize,
+    token: Secret,
+    outputs: Outputs,
+    scale: Scale,
+    client: reqwest::Client,
+}
+
+impl Coordinator {
+    /// `args` are the flags of the run, `load` and `workers` the totals
+    /// split between the agents, which only take load from whoever knows
+    /// `token`. The merged results are saved to `outputs`, with the latency
+    /// histogram drawn on `scale`.
+    pub fn new(
+        agents: Vec<String>,
+        args: Vec<String>,
+        load: usize,
+        workers: usize,
+        token: Secret,
+        outputs: Outputs,
+        scale: Scale,
+    ) -> Self {
+        let agents = agents.iter().map(|agent| parse_agent(agent)).collect();
+        Self {
+            agents,
+            args,
+            load,
+            workers,
+            token,
+            outputs,
+            scale,
+            client: reqwest::Client::builder()
+                .timeout(REQUEST_TIMEOUT)
+                .build()
+                .expect("Unable to build client"),
+        }
+    }
+
+    pub async fn run(&self) {
+        println!("Preparing {} agents...", self.agents.len());
+        let loads = split(self.load, self.agents.len());
+        let workers = split(self.workers, self.agents.len());
+        let prepared = future::join_all(self.agents.iter().zip(loads.zip(workers)).map(
+            |(agent, (load, workers))| {
+                let mut args = self.args.clone();
+                // The last occurrence of a flag wins, so these override the
+                // totals given for the whole run.
+                args.extend([
+                    String::from("--load"),
+                    load.to_string(),
+                    String::from("--workers"),
+                    workers.max(1).to_string(),
+                ]);
+                self.post(agent, "prepare", Job { args })
+            },
+        ))
+        .await;
+
+        let mut failed = false;
+        for (agent, res) in self.agents.iter().zip(&prepared) {
+            if let Err(e) = res {
+                eprintln!("Unable to prepare agent {}: {}", agent, e);
+                failed = true;
+            }
+        }
+        if failed {
+            process::exit(1);
+        }
+
+        println!("Starting {} agents...", self.agents.len());
+        let started = future::join_all(
+            self.agents
+                .iter()
+                .map(|agent| self.post(agent, "start", ())),
+        )
+        .await;
+        let reports = future::join_all(self.agents.iter().zip(started).map(
+            |(agent, started)| async move {
+                started?;
+                self.report(agent).await
+            },
+        ))
+        .await;
+
+        let mut merged: Option<Report> = None;
+        for (agent, report) in self.agents.iter().zip(reports) {
+            match report {
+                Ok(report) => merged
+                    .get_or_insert_with(|| {
+                        let mut merged = Report::new(report.hosts().to_vec());
+                        merged.set_scale(self.scale);
+                        merged
+                    })
+                    .merge(agent.as_str(), report),
+                Err(e) => {
+                    eprintln!("Agent {} failed: {}", agent, e);
+                    failed = true;
+                }
+            }
+        }
+
+        if let Some(report) = merged {
+            println!("{}", report);
+            if let Err(e) = self.outputs.save(&report) {
+                eprintln!("Unable to save the results: {}", e);
+                failed = true;
+            }
+        }
+        if failed {
+            process::exit(1);
+        }
+    }
+
+    async fn post<T: serde::Serialize>(
+        &self,
+        agent: &Url,
+        path: &str,
+        body: T,
+    ) -> Result<reqwest::Response, String> {
+        let url = agent.join(path).map_err(|e| e.to_string())?;
+        let res = self
+            .client
+            .post(url)
+            .bearer_auth(self.token.expose())
+            .json(&body)
+            .send()
+            .await
+            .map_err(|e| e.to_string())?;
+        if res.status().is_success() {
+            Ok(res)
+        } else {
+            Err(res.text().await.map_err(|e| e.to_string())?)
+        }
+    }
+
+    // Waits for `agent` to be done with its share of the run, and returns
+    // the report for it.
+    async fn report(&self, agent: &Url) -> Result<Report, String> {
+        let url = agent.join("report").map_err(|e| e.to_string())?;
+        loop {
+            let res = self
+                .client
+                .get(url.clone())
+                .bearer_auth(self.token.expose())
+                .send()
+                .await
+                .map_err(|e| e.to_string())?;
+            match res.status() {
+                StatusCode::OK => return res.json().await.map_err(|e| e.to_string()),
+                StatusCode::ACCEPTED => tokio::time::sleep(POLL_INTERVAL).await,
+                _ => return Err(res.text().await.map_err(|e| e.to_string())?),
+            }
+        }
+    }
+}
+
+// Agents can be given as host:port or as a URL.
+fn parse_agent(agent: &str) -> Url {
+    let url = if agent.contains("://") {
+        agent.to_string()
+    } else {
+        format!("http://{}", agent)
+    };
+    Url::parse(&url).unwrap_or_else(|e| panic!("{} is not a valid agent address: {}", agent, e))
+}
+
+// Splits `total` into `parts` that differ by at most one.
+fn split(total: usize, parts: usize) -> impl Iterator<Item = usize> {
+    (0..parts).map(move |i| total / parts + usize::from(i < total % parts))
+}
@@ -0,0 +1,27 @@
+use std::fmt;
+use std::io;
+
+/// Error sending a request or reading its response.
+#[derive(Debug)]
+pub enum Error {
+    Request(reqwest::Error),
+    /// The response body couldn't be decompressed.
+    Decode(io::Error),
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+impl From<reqwest::Error> for Error {
+    fn from(e: reqwest::Error) -> Self {
+        Error::Request(e)
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::Request(e) => write!(f, "{}", e),
+            Error::Decode(e) => write!(f, "unable to decode response body: {}", e),
+        }
+    }
+}
@@ -0,0 +1,323 @@
+use serde::{Deserialize, Serialize};
+
+use std::collections::BTreeMap;
+use std::fmt;
+use std::time::Duration;
+
+use crate::report;
+
+// Amount of buckets a linear histogram gets when no step is given.
+const LINEAR_BUCKETS: u128 = 20;
+// Most buckets a linear histogram shows. Slower latencies are counted in a
+// last `>= value` bucket, like the overflow row of DTrace's `lquantize`.
+const MAX_LINEAR_BUCKETS: usize = 100;
+// Width of the bars, in the style of DTrace's aggregations.
+const BAR_WIDTH: usize = 40;
+
+/// Every latency of a run, counted into buckets so a run takes the same
+/// memory however many requests it sends. The total, fastest and slowest
+/// latencies are kept exactly.
+///
+/// Like HdrHistogram, the buckets are log-linear: one per microsecond up to
+/// `2^PRECISION` microseconds, then `2^(PRECISION-1)` per power of two, so
+/// no bucket is wider than `1/2^(PRECISION-1)` of the latencies in it.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(from = "SavedLatencies", into = "SavedLatencies")]
+pub struct Latencies<const PRECISION: u32 = 10> {
+    count: u64,
+    total: Duration,
+    min: Duration,
+    max: Duration,
+    buckets: BTreeMap<u64, u64>,
+}
+
+impl<const PRECISION: u32> Latencies<PRECISION> {
+    pub fn record(&mut self, latency: Duration) {
+        self.min = if self.count == 0 {
+            latency
+        } else {
+            self.min.min(latency)
+        };
+        self.max = self.max.max(latency);
+        self.count += 1;
+        self.total += latency;
+        *self.buckets.entry(index::<PRECISION>(latency)).or_default() += 1;
+    }
+
+    pub fn merge(&mut self, other: &Self) {
+        if other.count == 0 {
+            return;
+        }
+        self.min = if self.count == 0 {
+            other.min
+        } else {
+            self.min.min(other.min)
+        };
+        self.max = self.max.max(other.max);
+        self.count += other.count;
+        self.total += other.total;
+        for (&bucket, &count) in &other.buckets {
+            *self.buckets.entry(bucket).or_default() += count;
+        }
+    }
+
+    pub fn count(&self) -> u64 {
+        self.count
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.count == 0
+    }
+
+    pub fn min(&self) -> Duration {
+        self.min
+    }
+
+    pub fn max(&self) -> Duration {
+        self.max
+    }
+
+    pub fn mean(&self) -> Duration {
+        match self.count {
+            0 => Duration::ZERO,
+            count => Duration::from_nanos((self.total.as_nanos() / count as u128) as u64),
+        }
+    }
+
+    /// Returns the `p`th percentile, using the nearest rank.
+    pub fn percentile(&self, p: f64) -> Duration {
+        let rank = ((p / 100.0 * self.count as f64).ceil() as u64).clamp(1, self.count.max(1));
+        let mut seen = 0;
+        for (value, count) in self.buckets() {
+            seen += count;
+            if seen >= rank {
+                return value.clamp(self.min, self.max);
+            }
+        }
+        Duration::ZERO
+    }
+
+    /// The middle of every bucket holding latencies, fastest first, with
+    /// how many it holds.
+    pub fn buckets(&self) -> impl Iterator<Item = (Duration, u64)> + '_ {
+        self.buckets.iter().map(|(&bucket, &count)| {
+            let (start, width) = bounds::<PRECISION>(bucket);
+            (Duration::from_nanos(start * 1000 + width * 500), count)
+        })
+    }
+}
+
+// Bucket of a latency, for `Latencies`.
+fn index<const PRECISION: u32>(latency: Duration) -> u64 {
+    let exact = 1u64 << PRECISION;
+    let us = latency.as_micros().min(u64::MAX as u128) as u64;
+    if us < exact {
+        return us;
+    }
+    let shift = (63 - us.leading_zeros()) - (PRECISION - 1);
+    exact + (shift as u64 - 1) * (exact / 2) + ((us >> shift) - exact / 2)
+}
+
+// Start and width of a bucket, in microseconds.
+fn bounds<const PRECISION: u32>(bucket: u64) -> (u64, u64) {
+    let exact = 1u64 << PRECISION;
+    if bucket < exact {
+        return (bucket, 1);
+    }
+    let shift = (bucket - exact) / (exact / 2) + 1;
+    let sub = (bucket - exact) % (exact / 2);
+    ((exact / 2 + sub) << shift, 1 << shift)
+}
+
+// Latencies as they're saved, with every bucket named after the
+// microsecond it starts at so saved reports don't depend on how buckets are
+// numbered.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct SavedLatencies {
+    count: u64,
+    total: Duration,
+    min: Duration,
+    max: Duration,
+    buckets: Vec<(u64, u64)>,
+}
+
+impl<const PRECISION: u32> From<Latencies<PRECISION>> for SavedLatencies {
+    fn from(latencies: Latencies<PRECISION>) -> Self {
+        Self {
+            count: latencies.count,
+            total: latencies.total,
+            min: latencies.min,
+            max: latencies.max,
+            buckets: latencies
+                .buckets
+                .into_iter()
+                .map(|(bucket, count)| (bounds::<PRECISION>(bucket).0, count))
+                .collect(),
+        }
+    }
+}
+
+impl<const PRECISION: u32> From<SavedLatencies> for Latencies<PRECISION> {
+    fn from(saved: SavedLatencies) -> Self {
+        let mut buckets = BTreeMap::new();
+        for (start, count) in saved.buckets {
+            *buckets
+                .entry(index::<PRECISION>(Duration::from_micros(start)))
+                .or_default() += count;
+        }
+        Self {
+            count: saved.count,
+            total: saved.total,
+            min: saved.min,
+            max: saved.max,
+            buckets,
+        }
+    }
+}
+
+/// How latencies are split into buckets.
+#[derive(Clone, Copy, Debug, Default)]
+pub enum Scale {
+    /// Power-of-two buckets, like DTrace's `quantize`.
+    #[default]
+    Quantize,
+    /// Buckets of the same width, like DTrace's `lquantize`. The width is
+    /// picked from the spread of the latencies when not given.
+    Linear(Option<Duration>),
+}
+
+/// Latencies counted into buckets, to show how they're distributed.
+#[derive(Debug)]
+pub struct Histogram {
+    pub buckets: Vec<Bucket>,
+}
+
+/// Amount of latencies from `start` up to the start of the next bucket, or
+/// from `start` up when the bucket is an `overflow`.
+#[derive(Debug)]
+pub struct Bucket {
+    pub start: Duration,
+    pub count: usize,
+    pub overflow: bool,
+}
+
+impl Histogram {
+    pub fn new(latencies: &Latencies, scale: Scale) -> Self {
+        match scale {
+            Scale::Quantize => Self::quantize(latencies),
+            Scale::Linear(step) => Self::linear(latencies, step),
+        }
+    }
+
+    /// Counts `latencies` into power-of-two buckets of microseconds, like
+    /// DTrace's `quantize`.
+    pub fn quantize(latencies: &Latencies) -> Self {
+        // Bucket 0 holds anything under a microsecond, bucket n the
+        // latencies from 2^(n-1) up to 2^n microseconds.
+        Self::count(
+            latencies,
+            |d| match d.as_micros() as u64 {
+                0 => 0,
+                us => 64 - us.leading_zeros() as usize,
+            },
+            |i| match i {
+                0 => Duration::ZERO,
+                i => Duration::from_micros(1 << (i - 1)),
+            },
+            usize::MAX,
+        )
+    }
+
+    /// Counts `latencies` into buckets `step` wide, like DTrace's
+    /// `lquantize`. At most `MAX_LINEAR_BUCKETS` are kept, however small the
+    /// step.
+    pub fn linear(latencies: &Latencies, step: Option<Duration>) -> Self {
+        let step = step.unwrap_or_else(|| {
+            let step = (latencies.max().as_micros() / LINEAR_BUCKETS).max(1) as u64;
+            // Rounded up to 1, 2 or 5 times a power of ten microseconds so
+            // the values stay readable.
+            let magnitude = 10u64.pow(step.ilog10());
+            let round = [1, 2, 5, 10]
+                .into_iter()
+                .map(|m| m * magnitude)
+                .find(|&round| round >= step)
+                .unwrap_or(step);
+            Duration::from_micros(round)
+        });
+        let step = step.as_nanos().max(1);
+        Self::count(
+            latencies,
+            |d| (d.as_nanos() / step) as usize,
+            |i| Duration::from_nanos((i as u128 * step) as u64),
+            MAX_LINEAR_BUCKETS,
+        )
+    }
+
+    // Counts `latencies` into the buckets `index` puts them in, with `bound`
+    // the start of each bucket. Like DTrace, every bucket from the one
+    // before the fastest latency to the one after the slowest is kept, even
+    // when it's empty, up to `limit` buckets. Past that, the last bucket
+    // counts everything from its start up.
+    fn count(
+        latencies: &Latencies,
+        index: impl Fn(&Duration) -> usize,
+        bound: impl Fn(usize) -> Duration,
+        limit: usize,
+    ) -> Self {
+        if latencies.is_empty() {
+            return Self {
+                buckets: Vec::new(),
+            };
+        }
+        let first = index(&latencies.min()).saturating_sub(1);
+        let last = index(&latencies.max()) + 1;
+        let overflow = last - first >= limit;
+        let last = if overflow { first + limit - 1 } else { last };
+
+        let mut buckets: Vec<Bucket> = (first..=last)
+            .map(|i| Bucket {
+                start: bound(i),
+                count: 0,
+                overflow: overflow && i == last,
+            })
+            .collect();
+        // The middle of a bucket can lie past the fastest or slowest
+        // latency, which are kept exactly, and so outside the buckets.
+        for (latency, count) in latencies.buckets() {
+            let latency = latency.clamp(latencies.min(), latencies.max());
+            buckets[index(&latency).min(last) - first].count += count as usize;
+        }
+
+        Self { buckets }
+    }
+}
+
+impl fmt::Display for Histogram {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let total: usize = self.buckets.iter().map(|b| b.count).sum();
+        writeln!(
+            f,
+            "  {:>16} {:-^width$} count",
+            "value",
+            " Distribution ",
+            width = BAR_WIDTH + 2
+        )?;
+        for bucket in &self.buckets {
+            let bar = (bucket.count * BAR_WIDTH + total / 2) / total.max(1);
+            let value = if bucket.overflow {
+                format!(">= {}", report::millis(bucket.start))
+            } else {
+                report::millis(bucket.start)
+            };
+            writeln!(
+                f,
+                "  {:>16} |{:<width$} {}",
+                value,
+                "@".repeat(bar),
+                bucket.count,
+                width = BAR_WIDTH
+            )?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,310 @@
+use std::fmt;
+use std::time::Duration;
+
+use crate::histogram::Histogram;
+use crate::report::{self, Report};
+
+const WIDTH: f64 = 720.0;
+const HEIGHT: f64 = 260.0;
+const MARGIN: f64 = 56.0;
+
+const STYLE: &str = "body{font-family:sans-serif;margin:2em auto;max-width:760px;color:#222}\
+h2{font-size:1.1em;margin-top:2em}\
+svg{background:#fafafa;border:1px solid #ddd}\
+svg text{font-size:11px;fill:#444}\
+.grid{stroke:#e4e4e4}\
+.axis{stroke:#888}\
+pre{background:#f4f4f4;padding:1em;overflow-x:auto}";
+
+/// Renders a report as a single HTML page. The charts are inline SVG, so
+/// the page can be viewed without network access.
+pub struct Page<'a>(pub &'a Report);
+
+// A line on a chart over time, as (seconds into the run, value) points.
+struct Series {
+    name: &'static str,
+    color: &'static str,
+    points: Vec<(f64, f64)>,
+}
+
+// A bar on a bar chart.
+struct Bar {
+    label: String,
+    color: &'static str,
+    count: u64,
+}
+
+impl fmt::Display for Page<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let report = self.0;
+        writeln!(f, "<!DOCTYPE html>")?;
+        writeln!(f, "<html lang=\"en\">")?;
+        writeln!(f, "<head>")?;
+        writeln!(f, "<meta charset=\"utf-8\">")?;
+        writeln!(f, "<title>burst report</title>")?;
+        writeln!(f, "<style>{}</style>", STYLE)?;
+        writeln!(f, "</head>")?;
+        writeln!(f, "<body>")?;
+        writeln!(f, "<h1>burst report</h1>")?;
+        for host in report.hosts() {
+            writeln!(f, "<p>{}</p>", escape(host))?;
+        }
+
+        let elapsed = report.elapsed().as_secs_f64();
+        let (latency, throughput) = over_time(report);
+        writeln!(f, "<h2>Latency over time</h2>")?;
+        line_chart(f, elapsed, "ms", &latency)?;
+        writeln!(f, "<h2>Throughput over time</h2>")?;
+        line_chart(f, elapsed, "req/s", &throughput)?;
+        writeln!(f, "<h2>Status codes</h2>")?;
+        bar_chart(f, &statuses(report))?;
+        writeln!(f, "<h2>Latency distribution</h2>")?;
+        bar_chart(f, &distribution(report))?;
+
+        writeln!(f, "<h2>Summary</h2>")?;
+        writeln!(f, "<pre>{}</pre>", escape(&report.to_string()))?;
+        writeln!(f, "</body>")?;
+        writeln!(f, "</html>")
+    }
+}
+
+// Average and p99 latency, and responses and errors per second, for every
+// slice of the run's timeline.
+fn over_time(report: &Report) -> (Vec<Series>, Vec<Series>) {
+    let timeline = report.timeline();
+    let seconds = timeline.slice.as_secs_f64();
+    let mut average = Vec::new();
+    let mut p99 = Vec::new();
+    let mut responses = Vec::new();
+    let mut failures = Vec::new();
+    for (i, slice) in timeline.slices.iter().enumerate() {
+        let at = (i as f64 + 0.5) * seconds;
+        responses.push((at, slice.latencies.count() as f64 / seconds));
+        failures.push((at, slice.errors as f64 / seconds));
+        if slice.latencies.is_empty() {
+            continue;
+        }
+        average.push((at, ms(slice.latencies.mean())));
+        p99.push((at, ms(slice.latencies.percentile(99.0))));
+    }
+
+    (
+        vec![
+            Series {
+                name: "average",
+                color: "#1f77b4",
+                points: average,
+            },
+            Series {
+                name: "p99",
+                color: "#d62728",
+                points: p99,
+            },
+        ],
+        vec![
+            Series {
+                name: "responses",
+                color: "#2ca02c",
+                points: responses,
+            },
+            Series {
+                name: "errors",
+                color: "#d62728",
+                points: failures,
+            },
+        ],
+    )
+}
+
+fn statuses(report: &Report) -> Vec<Bar> {
+    let total = report.total();
+    let statuses = total.statuses.iter().map(|(&status, &count)| Bar {
+        label: status.to_string(),
+        color: match status {
+            200..=299 => "#2ca02c",
+            300..=399 => "#1f77b4",
+            400..=499 => "#ff7f0e",
+            _ => "#d62728",
+        },
+        count,
+    });
+    let errors = total.errors.iter().map(|(class, &count)| Bar {
+        label: class.to_string(),
+        color: "#7f7f7f",
+        count,
+    });
+    statuses.chain(errors).collect()
+}
+
+fn distribution(report: &Report) -> Vec<Bar> {
+    Histogram::quantize(&report.total().latencies)
+        .buckets
+        .into_iter()
+        .map(|bucket| Bar {
+            label: report::millis(bucket.start),
+            color: "#1f77b4",
+            count: bucket.count as u64,
+        })
+        .collect()
+}
+
+fn line_chart(f: &mut fmt::Formatter, x_max: f64, unit: &str, series: &[Series]) -> fmt::Result {
+    let y_max = series
+        .iter()
+        .flat_map(|s| s.points.iter().map(|&(_, y)| y))
+        .fold(0.0, f64::max);
+    let y_max = if y_max > 0.0 { y_max } else { 1.0 };
+    let x_max = if x_max > 0.0 { x_max } else { 1.0 };
+    let x = |v: f64| MARGIN + v / x_max * (WIDTH - 2.0 * MARGIN);
+    let y = |v: f64| HEIGHT - MARGIN - v / y_max * (HEIGHT - 2.0 * MARGIN);
+
+    svg_open(f)?;
+    y_axis(f, y_max, |v| format!("{:.1}{}", v, unit))?;
+    writeln!(
+        f,
+        "<text x=\"{:.1}\" y=\"{:.1}\" text-anchor=\"middle\">0s</text>",
+        x(0.0),
+        HEIGHT - MARGIN + 16.0
+    )?;
+    writeln!(
+        f,
+        "<text x=\"{:.1}\" y=\"{:.1}\" text-anchor=\"middle\">{:.3}s</text>",
+        x(x_max),
+        HEIGHT - MARGIN + 16.0,
+        x_max
+    )?;
+
+    for (i, s) in series.iter().enumerate() {
+        let points: Vec<(f64, f64)> = s.points.iter().map(|&(px, py)| (x(px), y(py))).collect();
+        let line: Vec<String> = points
+            .iter()
+            .map(|(px, py)| format!("{:.1},{:.1}", px, py))
+            .collect();
+        writeln!(
+            f,
+            "<polyline fill=\"none\" stroke=\"{}\" stroke-width=\"2\" points=\"{}\"/>",
+            s.color,
+            line.join(" ")
+        )?;
+        for (px, py) in points {
+            writeln!(
+                f,
+                "<circle cx=\"{:.1}\" cy=\"{:.1}\" r=\"2\" fill=\"{}\"/>",
+                px, py, s.color
+            )?;
+        }
+
+        let legend = MARGIN + 110.0 * i as f64;
+        writeln!(
+            f,
+            "<rect x=\"{:.1}\" y=\"12\" width=\"12\" height=\"12\" fill=\"{}\"/>",
+            legend, s.color
+        )?;
+        writeln!(
+            f,
+            "<text x=\"{:.1}\" y=\"22\">{}</text>",
+            legend + 16.0,
+            s.name
+        )?;
+    }
+    writeln!(f, "</svg>")
+}
+
+fn bar_chart(f: &mut fmt::Formatter, bars: &[Bar]) -> fmt::Result {
+    let y_max = bars.iter().map(|b| b.count).max().unwrap_or(0).max(1) as f64;
+    let slot = (WIDTH - 2.0 * MARGIN) / bars.len().max(1) as f64;
+    let height = HEIGHT - 2.0 * MARGIN;
+
+    svg_open(f)?;
+    y_axis(f, y_max, |v| format!("{:.0}", v))?;
+    for (i, bar) in bars.iter().enumerate() {
+        let h = bar.count as f64 / y_max * height;
+        let left = MARGIN + slot * i as f64;
+        let center = left + slot / 2.0;
+        writeln!(
+            f,
+            "<rect x=\"{:.1}\" y=\"{:.1}\" width=\"{:.1}\" height=\"{:.1}\" fill=\"{}\"><title>{}: {}</title></rect>",
+            left + slot * 0.1,
+            HEIGHT - MARGIN - h,
+            slot * 0.8,
+            h,
+            bar.color,
+            escape(&bar.label),
+            bar.count
+        )?;
+        writeln!(
+            f,
+            "<text x=\"{:.1}\" y=\"{:.1}\" text-anchor=\"middle\">{}</text>",
+            center,
+            HEIGHT - MARGIN - h - 4.0,
+            bar.count
+        )?;
+        // Labels are turned sideways so they don't overlap on busy charts.
+        writeln!(
+            f,
+            "<text x=\"{:.1}\" y=\"{:.1}\" text-anchor=\"end\" transform=\"rotate(-45 {:.1} {:.1})\">{}</text>",
+            center,
+            HEIGHT - MARGIN + 14.0,
+            center,
+            HEIGHT - MARGIN + 14.0,
+            escape(&bar.label)
+        )?;
+    }
+    writeln!(f, "</svg>")
+}
+
+fn svg_open(f: &mut fmt::Formatter) -> fmt::Result {
+    writeln!(
+        f,
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{}\" height=\"{}\" viewBox=\"0 0 {} {}\">",
+        WIDTH, HEIGHT, WIDTH, HEIGHT
+    )
+}
+
+// Draws the axes with a few gridlines, labelled with `label`.
+fn y_axis(f: &mut fmt::Formatter, y_max: f64, label: impl Fn(f64) -> String) -> fmt::Result {
+    let bottom = HEIGHT - MARGIN;
+    for step in 0..=4 {
+        let value = y_max * step as f64 / 4.0;
+        let y = bottom - (HEIGHT - 2.0 * MARGIN) * step as f64 / 4.0;
+        writeln!(
+            f,
+            "<line class=\"grid\" x1=\"{}\" y1=\"{:.1}\" x2=\"{}\" y2=\"{:.1}\"/>",
+            MARGIN,
+            y,
+            WIDTH - MARGIN,
+            y
+        )?;
+        writeln!(
+            f,
+            "<text x=\"{}\" y=\"{:.1}\" text-anchor=\"end\">{}</text>",
+            MARGIN - 4.0,
+            y + 4.0,
+            label(value)
+        )?;
+    }
+    writeln!(
+        f,
+        "<line class=\"axis\" x1=\"{}\" y1=\"{}\" x2=\"{}\" y2=\"{}\"/>",
+        MARGIN, MARGIN, MARGIN, bottom
+    )?;
+    writeln!(
+        f,
+        "<line class=\"axis\" x1=\"{}\" y1=\"{}\" x2=\"{}\" y2=\"{}\"/>",
+        MARGIN,
+        bottom,
+        WIDTH - MARGIN,
+        bottom
+    )
+}
+
+fn ms(d: Duration) -> f64 {
+    d.as_secs_f64() * 1000.0
+}
+
+fn escape(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
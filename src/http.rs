@@ -0,0 +1,269 @@
+use hyper::client::connect::HttpInfo;
+use reqwest::header::CONNECTION;
+use reqwest::redirect::Policy;
+use reqwest::Url;
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+
+use crate::proxy::ProxyOptions;
+use crate::tls::TlsOptions;
+
+use std::collections::BTreeMap;
+use std::hash::{DefaultHasher, Hash, Hasher};
+use std::net::{IpAddr, SocketAddr};
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+// Slots connections are remembered in, to tell which response is the first
+// over its connection.
+const CONNECTION_SLOTS: usize = 4096;
+
+/// HTTP protocol version burst will use to talk to the host.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum HttpVersion {
+    /// HTTP/1.1 only, this is what burst has always sent.
+    Http1,
+    /// Offers HTTP/2 through ALPN and falls back to HTTP/1.1 if the host
+    /// doesn't support it.
+    Http2,
+    /// Speaks HTTP/2 straight away without any negotiation. Needed for
+    /// cleartext h2c hosts.
+    Http2PriorKnowledge,
+}
+
+#[derive(Clone, Debug)]
+pub struct HttpOptions {
+    pub version: HttpVersion,
+    pub max_concurrent_streams: Option<usize>,
+    pub tls: TlsOptions,
+    /// Addresses to connect to instead of resolving the host through DNS.
+    /// The Host header and SNI are still set from the request URL, and the
+    /// port is the one the request URL uses.
+    pub resolve: BTreeMap<String, Vec<SocketAddr>>,
+    pub proxy: ProxyOptions,
+    /// Maximum amount of redirects to follow per request. burst follows
+    /// them itself, reqwest is told to leave redirects alone.
+    pub max_redirects: usize,
+}
+
+impl HttpOptions {
+    pub fn build_client(&self, timeout: u64) -> reqwest::Client {
+        let user_agent: &str = concat!(env!("CARGO_PKG_NAME"), "/", env!("CARGO_PKG_VERSION"));
+        let builder = reqwest::Client::builder()
+            .timeout(Duration::from_secs(timeout))
+            .user_agent(user_agent)
+            .redirect(Policy::none());
+
+        let builder = match self.version {
+            HttpVersion::Http1 => builder.http1_only(),
+            HttpVersion::Http2 => builder,
+            HttpVersion::Http2PriorKnowledge => builder.http2_prior_knowledge(),
+        };
+
+        let builder = self.resolve.iter().fold(builder, |builder, (host, addrs)| {
+            builder.resolve_to_addrs(host, addrs)
+        });
+
+        let builder = self.proxy.apply(builder);
+
+        self.tls
+            .apply(builder)
+            .build()
+            .expect("Unable to build client")
+    }
+}
+
+/// Parses a curl style `host:port:addr[,addr]...` DNS override. IPv6
+/// addresses need to be wrapped in brackets.
+pub fn parse_resolve(resolve: &str) -> Result<(String, Vec<SocketAddr>), String> {
+    let invalid = || {
+        format!(
+            "{} is not a valid DNS override. Use the host:port:addr[,addr]... format.",
+            resolve
+        )
+    };
+
+    let mut parts = resolve.splitn(3, ':');
+    let (host, port, addrs) = match (parts.next(), parts.next(), parts.next()) {
+        (Some(host), Some(port), Some(addrs)) if !host.is_empty() => (host, port, addrs),
+        _ => return Err(invalid()),
+    };
+    let port: u16 = port.parse().map_err(|_| invalid())?;
+
+    let addrs = addrs
+        .split(',')
+        .map(|addr| {
+            let addr = addr.trim_start_matches('[').trim_end_matches(']');
+            let ip: IpAddr = addr.parse().map_err(|_| invalid())?;
+            Ok(SocketAddr::new(ip, port))
+        })
+        .collect::<Result<_, String>>()?;
+
+    Ok((host.to_string(), addrs))
+}
+
+/// Collects the DNS overrides given with `--resolve` for a run sending
+/// requests to `hosts`.
+///
+/// reqwest looks overrides up by host alone and connects to the port of the
+/// request URL, so an override would apply to the host on every port. Rather
+/// than send requests for another port to the pinned addresses, a host can
+/// only be pinned on one port, and it has to be the one the hosts use.
+pub fn resolve_overrides<'a>(
+    values: impl IntoIterator<Item = &'a str>,
+    hosts: &[String],
+) -> Result<BTreeMap<String, Vec<SocketAddr>>, String> {
+    let mut resolve: BTreeMap<String, Vec<SocketAddr>> = BTreeMap::new();
+    for value in values {
+        let (host, addrs) = parse_resolve(value)?;
+        let pinned = resolve.entry(host.clone()).or_default();
+        if let (Some(pinned), Some(addr)) = (pinned.first(), addrs.first()) {
+            if pinned.port() != addr.port() {
+                return Err(format!(
+                    "{} is pinned on ports {} and {}. A host can only be pinned on one port.",
+                    host,
+                    pinned.port(),
+                    addr.port()
+                ));
+            }
+        }
+        pinned.extend(addrs);
+    }
+
+    for target in hosts {
+        let url = match Url::parse(target) {
+            Ok(url) => url,
+            Err(_) => continue,
+        };
+        let (host, port) = match (url.host_str(), url.port_or_known_default()) {
+            (Some(host), Some(port)) => (host, port),
+            _ => continue,
+        };
+        if let Some(addr) = resolve.get(host).and_then(|addrs| addrs.first()) {
+            if addr.port() != port {
+                return Err(format!(
+                    "{} is pinned on port {}, but {} uses port {}. Pin it on the port the requests are sent to.",
+                    host,
+                    addr.port(),
+                    target,
+                    port
+                ));
+            }
+        }
+    }
+
+    Ok(resolve)
+}
+
+#[derive(Clone, Debug)]
+struct Connection {
+    client: reqwest::Client,
+    streams: Option<Arc<Semaphore>>,
+}
+
+/// Set of reqwest clients requests are sent through.
+///
+/// reqwest multiplexes every HTTP/2 request to a host over a single
+/// connection, so when the amount of concurrent streams per connection
+/// is capped, burst builds one client (and therefore one connection pool)
+/// per group of streams and hands requests out to whichever has room.
+#[derive(Clone, Debug)]
+pub struct Connections {
+    pool: Arc<Vec<Connection>>,
+    next: Arc<AtomicUsize>,
+    proxy: Arc<ProxyOptions>,
+    // Fingerprint of the local and remote address of the last connection a
+    // response came over, in the slot the fingerprint picks.
+    seen: Arc<[AtomicU64]>,
+}
+
+impl Connections {
+    pub fn new(options: &HttpOptions, timeout: u64, workers: usize) -> Self {
+        let pool = match options.max_concurrent_streams {
+            Some(streams) => {
+                let connections = workers.div_ceil(streams);
+                (0..connections.max(1))
+                    .map(|_| Connection {
+                        client: options.build_client(timeout),
+                        streams: Some(Arc::new(Semaphore::new(streams))),
+                    })
+                    .collect()
+            }
+            None => vec![Connection {
+                client: options.build_client(timeout),
+                streams: None,
+            }],
+        };
+
+        Self {
+            pool: Arc::new(pool),
+            next: Arc::new(AtomicUsize::new(0)),
+            proxy: Arc::new(options.proxy.clone()),
+            seen: (0..CONNECTION_SLOTS).map(|_| AtomicU64::new(0)).collect(),
+        }
+    }
+
+    /// Whether requests to `url` go through a proxy.
+    pub fn is_proxied(&self, url: &str) -> bool {
+        self.proxy.is_proxied(url)
+    }
+
+    /// Whether `res` is the first response to come over its connection.
+    ///
+    /// reqwest doesn't say when it opens a connection, nor lets anything be
+    /// attached to one, so connections are told apart by their addresses.
+    /// They're remembered in a fixed amount of slots rather than all kept,
+    /// and forgotten once a response says the connection is closing, so a
+    /// new connection from the same port counts as opened again. Two open
+    /// connections sharing a slot can make one count as opened twice.
+    pub fn opened(&self, res: &reqwest::Response) -> bool {
+        let info = match res.extensions().get::<HttpInfo>() {
+            Some(info) => info,
+            None => return false,
+        };
+        let mut hasher = DefaultHasher::new();
+        (info.local_addr(), info.remote_addr()).hash(&mut hasher);
+        // 0 marks an empty slot.
+        let fingerprint = hasher.finish().max(1);
+        let slot = &self.seen[fingerprint as usize % self.seen.len()];
+
+        let closing = res
+            .headers()
+            .get_all(CONNECTION)
+            .iter()
+            .any(|value| value.as_bytes().eq_ignore_ascii_case(b"close"));
+        if closing {
+            slot.compare_exchange(fingerprint, 0, Ordering::Relaxed, Ordering::Relaxed)
+                .is_err()
+        } else {
+            slot.swap(fingerprint, Ordering::Relaxed) != fingerprint
+        }
+    }
+
+    /// Returns a client with room for another request. The permit must be
+    /// held for as long as the request is in flight.
+    pub async fn acquire(&self) -> (reqwest::Client, Option<OwnedSemaphorePermit>) {
+        let start = self.next.fetch_add(1, Ordering::Relaxed);
+        let len = self.pool.len();
+
+        for i in 0..len {
+            let conn = &self.pool[(start + i) % len];
+            match &conn.streams {
+                None => return (conn.client.clone(), None),
+                Some(streams) => {
+                    if let Ok(permit) = streams.clone().try_acquire_owned() {
+                        return (conn.client.clone(), Some(permit));
+                    }
+                }
+            }
+        }
+
+        // Every connection is at its stream limit, wait in line on one of them.
+        let conn = &self.pool[start % len];
+        let permit = match &conn.streams {
+            Some(streams) => streams.clone().acquire_owned().await.ok(),
+            None => None,
+        };
+        (conn.client.clone(), permit)
+    }
+}
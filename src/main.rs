@@ -1,20 +1,62 @@
-#![cfg_attr(target_os = "macos", feature(asm_sym))]
-
 extern crate clap;
 
+use std::process;
 use std::sync::Arc;
 
-use usdt::dtrace_provider;
-
-use app::burst_app;
+use app::Command;
 
+mod agent;
 mod app;
+mod auth;
+mod body;
 mod client;
+mod compare;
+mod compression;
+mod config;
+mod coordinator;
+mod error;
+mod histogram;
+mod html;
+mod http;
+mod monitor;
+mod probes;
+mod proxy;
+mod redirect;
+mod report;
+mod sampler;
+mod secret;
+mod targets;
+mod tls;
+mod trace;
 
-dtrace_provider!("src/burst.d");
+fn main() {
+    let matches = app::matches();
+    let runtime = app::runtime(&matches);
 
-#[tokio::main]
-async fn main() {
-    let client = Arc::new(burst_app());
-    client.send_load().await;
+    // The client is built on the runtime, so it can keep track of it.
+    runtime.block_on(async {
+        match app::command(&matches) {
+            Command::Run(client) => Arc::new(client).send_load().await,
+            Command::Report(report, outputs) => {
+                println!("{}", report);
+                if let Err(e) = outputs.save(&report) {
+                    eprintln!("Unable to save the results: {}", e);
+                    process::exit(1);
+                }
+            }
+            Command::Compare(comparison) => {
+                println!("{}", comparison);
+                if comparison.regressed() {
+                    process::exit(1);
+                }
+            }
+            Command::Validate(Ok(valid)) => println!("{}", valid),
+            Command::Validate(Err(e)) => {
+                eprintln!("{}", e);
+                process::exit(1);
+            }
+            Command::Agent(addr, token) => agent::listen(addr, token).await,
+            Command::Coordinator(coordinator) => coordinator.run().await,
+        }
+    });
 }
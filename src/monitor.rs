@@ -0,0 +1,185 @@
+use serde::{Deserialize, Serialize};
+use tokio::runtime::Handle;
+
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::thread;
+use std::time::{Duration, Instant};
+
+// Past these, burst is considered to have been the bottleneck.
+const MAX_WORKER_BUSY: f64 = 0.9;
+const MAX_CPU: f64 = 0.9;
+const MAX_AVERAGE_LAG: Duration = Duration::from_millis(10);
+
+/// Keeps an eye on burst itself while it sends load, so a load generator
+/// that ran out of CPU doesn't get mistaken for a slow host.
+#[derive(Debug)]
+pub struct Monitor {
+    runtime: Option<Handle>,
+    baseline: Mutex<Baseline>,
+    lag_total: AtomicU64,
+    lag_max: AtomicU64,
+    lag_count: AtomicU64,
+}
+
+// Where the counters stood when the run started.
+#[derive(Debug)]
+struct Baseline {
+    started: Instant,
+    busy: Duration,
+    cpu: Duration,
+}
+
+impl Baseline {
+    fn new(runtime: Option<&Handle>) -> Self {
+        Self {
+            started: Instant::now(),
+            busy: runtime.map(busy).unwrap_or_default(),
+            cpu: rusage().0,
+        }
+    }
+}
+
+/// Snapshot of how hard burst was working.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Usage {
+    pub elapsed: Duration,
+    pub cores: usize,
+    pub cpu: Duration,
+    /// Peak resident memory in bytes.
+    pub max_rss: u64,
+    pub workers: usize,
+    /// Share of the run the tokio workers spent busy, from 0 to 1.
+    pub worker_busy: f64,
+    pub average_lag: Duration,
+    pub max_lag: Duration,
+}
+
+impl Monitor {
+    pub fn new() -> Self {
+        let runtime = Handle::try_current().ok();
+        Self {
+            baseline: Mutex::new(Baseline::new(runtime.as_ref())),
+            runtime,
+            lag_total: AtomicU64::new(0),
+            lag_max: AtomicU64::new(0),
+            lag_count: AtomicU64::new(0),
+        }
+    }
+
+    /// Counts CPU and worker busy time from now on, so building the client
+    /// isn't put down to the run.
+    pub fn start(&self) {
+        *self.baseline.lock().unwrap() = Baseline::new(self.runtime.as_ref());
+    }
+
+    /// Records how long after it was due a request actually started.
+    pub fn record_lag(&self, lag: Duration) {
+        let lag = lag.as_nanos() as u64;
+        self.lag_total.fetch_add(lag, Ordering::Relaxed);
+        self.lag_max.fetch_max(lag, Ordering::Relaxed);
+        self.lag_count.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn usage(&self) -> Usage {
+        let baseline = self.baseline.lock().unwrap();
+        let elapsed = baseline.started.elapsed();
+        let (cpu, max_rss) = rusage();
+
+        let workers = self
+            .runtime
+            .as_ref()
+            .map(|runtime| runtime.metrics().num_workers())
+            .unwrap_or(0);
+        let worker_busy = match &self.runtime {
+            Some(runtime) if workers > 0 && !elapsed.is_zero() => {
+                // Busy time is only counted once a worker goes idle, so a
+                // short run can see a little more than it spent.
+                let busy = busy(runtime).saturating_sub(baseline.busy);
+                (busy.as_secs_f64() / (elapsed.as_secs_f64() * workers as f64)).min(1.0)
+            }
+            _ => 0.0,
+        };
+
+        let lag_count = self.lag_count.load(Ordering::Relaxed);
+        let average_lag = match lag_count {
+            0 => Duration::ZERO,
+            n => Duration::from_nanos(self.lag_total.load(Ordering::Relaxed) / n),
+        };
+
+        Usage {
+            elapsed,
+            cores: thread::available_parallelism().map_or(1, |n| n.get()),
+            cpu: cpu.saturating_sub(baseline.cpu),
+            max_rss,
+            workers,
+            worker_busy,
+            average_lag,
+            max_lag: Duration::from_nanos(self.lag_max.load(Ordering::Relaxed)),
+        }
+    }
+}
+
+impl Usage {
+    /// Share of the available cores burst kept busy, from 0 to 1.
+    pub fn cpu_usage(&self) -> f64 {
+        if self.elapsed.is_zero() {
+            return 0.0;
+        }
+        self.cpu.as_secs_f64() / (self.elapsed.as_secs_f64() * self.cores as f64)
+    }
+
+    /// Returns why burst was the bottleneck during the run, if it was.
+    pub fn saturation(&self) -> Option<&'static str> {
+        if self.worker_busy >= MAX_WORKER_BUSY {
+            Some("the tokio workers were busy for most of the run")
+        } else if self.cpu_usage() >= MAX_CPU {
+            Some("burst used most of the available CPU")
+        } else if self.average_lag >= MAX_AVERAGE_LAG {
+            Some("requests started late on average")
+        } else {
+            None
+        }
+    }
+}
+
+// Total time the runtime's workers have spent busy since it started.
+fn busy(runtime: &Handle) -> Duration {
+    let metrics = runtime.metrics();
+    (0..metrics.num_workers())
+        .map(|worker| metrics.worker_total_busy_duration(worker))
+        .sum()
+}
+
+// CPU time used by the process so far and its peak resident memory in bytes.
+#[cfg(unix)]
+fn rusage() -> (Duration, u64) {
+    let mut usage = std::mem::MaybeUninit::<libc::rusage>::uninit();
+    // Safety: getrusage only writes to the struct it's given.
+    let usage = unsafe {
+        if libc::getrusage(libc::RUSAGE_SELF, usage.as_mut_ptr()) != 0 {
+            return (Duration::ZERO, 0);
+        }
+        usage.assume_init()
+    };
+
+    let time = |t: libc::timeval| {
+        Duration::from_secs(t.tv_sec as u64) + Duration::from_micros(t.tv_usec as u64)
+    };
+    let cpu = time(usage.ru_utime) + time(usage.ru_stime);
+
+    // macOS reports the peak in bytes, everything else in kilobytes.
+    let max_rss = if cfg!(target_os = "macos") {
+        usage.ru_maxrss as u64
+    } else {
+        usage.ru_maxrss as u64 * 1024
+    };
+
+    (cpu, max_rss)
+}
+
+// Only unix has getrusage, elsewhere the CPU and memory use aren't known.
+#[cfg(not(unix))]
+fn rusage() -> (Duration, u64) {
+    (Duration::ZERO, 0)
+}
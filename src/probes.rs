@@ -0,0 +1,115 @@
+use reqwest::Method;
+#[cfg(feature = "probes")]
+use usdt::dtrace_provider;
+
+use std::time::Duration;
+
+use crate::report::ErrorClass;
+
+/// Whether burst was built with the `probes` feature, so work that's only
+/// needed to fire a probe can be skipped without it.
+pub const ENABLED: bool = cfg!(feature = "probes");
+
+#[cfg(feature = "probes")]
+dtrace_provider!("src/burst.d");
+
+// Fires a probe from `burst.d`. usdt only emits probes DTrace can find, so
+// on Linux the probe is also emitted as a SystemTap SDT note for bpftrace
+// and SystemTap, with the arguments given after the `;`.
+#[cfg(feature = "probes")]
+macro_rules! fire {
+    ($probe:ident, ($($arg:expr),*); $($sdt:expr),*) => {{
+        burst::$probe!(|| ($($arg),*));
+        #[cfg(target_os = "linux")]
+        probe::probe!(burst, $probe $(, $sdt)*);
+    }};
+}
+
+// Without the `probes` feature there's nothing to fire.
+#[cfg(not(feature = "probes"))]
+macro_rules! fire {
+    ($probe:ident, ($($arg:expr),*); $($sdt:expr),*) => {{
+        let _ = ($(&$arg,)* $(&$sdt,)*);
+    }};
+}
+
+/// A request is about to be sent to `url`.
+pub fn start(method: &Method, id: u64, url: &str) {
+    let (ptr, len) = (url.as_ptr(), url.len());
+    match *method {
+        Method::GET => fire!(get__start, (id, url); id, ptr, len),
+        Method::POST => fire!(post__start, (id, url); id, ptr, len),
+        Method::PUT => fire!(put__start, (id, url); id, ptr, len),
+        Method::PATCH => fire!(patch__start, (id, url); id, ptr, len),
+        _ => {}
+    }
+}
+
+/// A request is over, with a `status` of 0 when it failed.
+pub fn done(method: &Method, id: u64, status: u16, latency: Duration) {
+    let latency = nanos(latency);
+    match *method {
+        Method::GET => fire!(get__done, (id, status, latency); id, status, latency),
+        Method::POST => fire!(post__done, (id, status, latency); id, status, latency),
+        Method::PUT => fire!(put__done, (id, status, latency); id, status, latency),
+        Method::PATCH => fire!(patch__done, (id, status, latency); id, status, latency),
+        _ => {}
+    }
+}
+
+/// A batch of requests is about to be sent.
+pub fn requests_start(id: u64) {
+    fire!(requests__start, (id); id);
+}
+
+/// Every request of a batch is over.
+pub fn requests_done(id: u64) {
+    fire!(requests__done, (id); id);
+}
+
+/// A response came back from `url`.
+pub fn response(id: u64, status: u16, latency: Duration, url: &str) {
+    let latency = nanos(latency);
+    fire!(
+        response__status,
+        (id, status, latency, url);
+        id, status, latency, url.as_ptr(), url.len()
+    );
+}
+
+/// A request to `url` failed.
+pub fn error(id: u64, latency: Duration, class: ErrorClass, url: &str) {
+    let latency = nanos(latency);
+    let class = class.name();
+    fire!(
+        request__error,
+        (id, latency, class, url);
+        id, latency, class.as_ptr(), class.len(), url.as_ptr(), url.len()
+    );
+}
+
+/// A redirect to `url` is being followed.
+pub fn redirect(id: u64, redirects: usize, url: &str) {
+    let redirects = redirects as u64;
+    fire!(
+        request__redirect,
+        (id, redirects, url);
+        id, redirects, url.as_ptr(), url.len()
+    );
+}
+
+/// The response to request `id` came over a connection to `url` that
+/// hasn't been used before.
+pub fn connection(id: u64, url: &str) {
+    fire!(connection__open, (id, url); id, url.as_ptr(), url.len());
+}
+
+/// Batch number `batch` of a timed run is about to be sent.
+pub fn tick(id: u64, batch: u64, elapsed: Duration) {
+    let elapsed = nanos(elapsed);
+    fire!(batch__tick, (id, batch, elapsed); id, batch, elapsed);
+}
+
+fn nanos(d: Duration) -> u64 {
+    d.as_nanos() as u64
+}
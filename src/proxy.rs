@@ -0,0 +1,113 @@
+use reqwest::{ClientBuilder, Proxy, Url};
+
+use std::env;
+use std::sync::Arc;
+
+/// Proxies requests are sent through.
+///
+/// burst resolves the proxy for every request itself rather than leaving
+/// it to reqwest, so it knows which requests went through a proxy when
+/// classifying connection errors.
+#[derive(Clone, Debug, Default)]
+pub struct ProxyOptions {
+    pub http: Option<Url>,
+    pub https: Option<Url>,
+    pub no_proxy: Vec<String>,
+}
+
+impl ProxyOptions {
+    /// Uses `proxy` for every request when set, or falls back to the
+    /// `HTTP_PROXY` and `HTTPS_PROXY` environment variables. `no_proxy`
+    /// takes precedence over the `NO_PROXY` environment variable.
+    pub fn new(proxy: Option<&str>, no_proxy: Option<&str>) -> Result<Self, String> {
+        let (http, https) = match proxy {
+            Some(proxy) => {
+                let proxy = parse_proxy(proxy)?;
+                (Some(proxy.clone()), Some(proxy))
+            }
+            None => (
+                env_var(&["http_proxy", "HTTP_PROXY"])
+                    .map(|p| parse_proxy(&p))
+                    .transpose()?,
+                env_var(&["https_proxy", "HTTPS_PROXY"])
+                    .map(|p| parse_proxy(&p))
+                    .transpose()?,
+            ),
+        };
+
+        let no_proxy = no_proxy
+            .map(String::from)
+            .or_else(|| env_var(&["no_proxy", "NO_PROXY"]))
+            .unwrap_or_default();
+        let no_proxy = no_proxy
+            .split(',')
+            .map(|host| host.trim().trim_start_matches('.').to_lowercase())
+            .filter(|host| !host.is_empty())
+            .collect();
+
+        Ok(Self {
+            http,
+            https,
+            no_proxy,
+        })
+    }
+
+    /// Returns the proxy a request to `url` should go through, if any.
+    pub fn proxy_for(&self, url: &Url) -> Option<&Url> {
+        let host = url
+            .host_str()?
+            .trim_start_matches('[')
+            .trim_end_matches(']');
+        let bypassed = self.no_proxy.iter().any(|no_proxy| {
+            no_proxy == "*"
+                || host.eq_ignore_ascii_case(no_proxy)
+                || host.to_lowercase().ends_with(&format!(".{}", no_proxy))
+        });
+        if bypassed {
+            return None;
+        }
+
+        match url.scheme() {
+            "http" => self.http.as_ref(),
+            "https" => self.https.as_ref(),
+            _ => None,
+        }
+    }
+
+    pub fn is_proxied(&self, url: &str) -> bool {
+        Url::parse(url)
+            .map(|url| self.proxy_for(&url).is_some())
+            .unwrap_or(false)
+    }
+
+    pub fn apply(&self, builder: ClientBuilder) -> ClientBuilder {
+        // Turns off reqwest's own proxy detection, burst has already looked
+        // at the environment.
+        let builder = builder.no_proxy();
+        if self.http.is_none() && self.https.is_none() {
+            return builder;
+        }
+
+        let options = Arc::new(self.clone());
+        builder.proxy(Proxy::custom(move |url| options.proxy_for(url).cloned()))
+    }
+}
+
+fn env_var(names: &[&str]) -> Option<String> {
+    names
+        .iter()
+        .filter_map(|name| env::var(name).ok())
+        .find(|value| !value.is_empty())
+}
+
+fn parse_proxy(proxy: &str) -> Result<Url, String> {
+    let url =
+        Url::parse(proxy).map_err(|e| format!("{} is not a valid proxy URL: {}", proxy, e))?;
+    match url.scheme() {
+        "http" | "https" | "socks5" | "socks5h" => Ok(url),
+        scheme => Err(format!(
+            "{} is not a supported proxy scheme. Use one of: 'http', 'https', 'socks5', or 'socks5h'.",
+            scheme
+        )),
+    }
+}
@@ -0,0 +1,46 @@
+use reqwest::header::{
+    AUTHORIZATION, CONTENT_ENCODING, CONTENT_LENGTH, CONTENT_TYPE, COOKIE, LOCATION,
+    PROXY_AUTHORIZATION,
+};
+use reqwest::{Method, Request, Response, StatusCode};
+
+/// Builds the request that follows a redirect response, the same way
+/// browsers do. Returns None when `res` isn't a redirect burst can follow.
+///
+/// burst follows redirects itself instead of letting reqwest do it, so it
+/// can tell how many were followed before the final response.
+pub fn next_request(prev: &Request, res: &Response) -> Option<Request> {
+    let status = res.status();
+    if !status.is_redirection() {
+        return None;
+    }
+
+    let location = res.headers().get(LOCATION)?.to_str().ok()?;
+    let url = res.url().join(location).ok()?;
+
+    let mut req = prev.try_clone()?;
+
+    let to_get = match status {
+        StatusCode::SEE_OTHER => *prev.method() != Method::HEAD,
+        StatusCode::MOVED_PERMANENTLY | StatusCode::FOUND => *prev.method() == Method::POST,
+        StatusCode::TEMPORARY_REDIRECT | StatusCode::PERMANENT_REDIRECT => false,
+        _ => return None,
+    };
+    if to_get {
+        *req.method_mut() = Method::GET;
+        *req.body_mut() = None;
+        for header in [CONTENT_TYPE, CONTENT_LENGTH, CONTENT_ENCODING] {
+            req.headers_mut().remove(header);
+        }
+    }
+
+    // Credentials are only meant for the host they were sent to.
+    if url.origin() != prev.url().origin() {
+        for header in [AUTHORIZATION, PROXY_AUTHORIZATION, COOKIE] {
+            req.headers_mut().remove(header);
+        }
+    }
+
+    *req.url_mut() = url;
+    Some(req)
+}
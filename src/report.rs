@@ -0,0 +1,543 @@
+use reqwest::{StatusCode, Version};
+use serde::{Deserialize, Serialize};
+
+use std::collections::BTreeMap;
+use std::error::Error as _;
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+use crate::error::Error;
+use crate::histogram::{Histogram, Latencies, Scale};
+use crate::html::Page;
+use crate::monitor::Usage;
+
+// Most redirect destinations named in the report, and what the rest are
+// counted under.
+const MAX_URLS: usize = 100;
+const OTHER_URLS: &str = "other URLs";
+// Length of the slices a timeline starts with, and the most it keeps.
+const TIMELINE_SLICE: Duration = Duration::from_millis(1);
+const TIMELINE_SLICES: usize = 128;
+// Precision of the latencies of every slice, coarser than for the whole run
+// as they're only charted.
+const TIMELINE_PRECISION: u32 = 6;
+
+/// Broad category of a failed request, so that e.g. a misconfigured
+/// certificate doesn't get mistaken for the host being slow.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ErrorClass {
+    Timeout,
+    Connect,
+    Tls,
+    Proxy,
+    Body,
+    Other,
+}
+
+impl ErrorClass {
+    /// Classifies a request error. Connection errors for requests sent
+    /// through a proxy are blamed on the proxy, as that's what burst
+    /// connects to.
+    pub fn from_error(e: &Error, proxied: bool) -> Self {
+        let e = match e {
+            Error::Request(e) => e,
+            Error::Decode(_) => return ErrorClass::Body,
+        };
+
+        let mut source = e.source();
+        while let Some(err) = source {
+            if err.downcast_ref::<native_tls::Error>().is_some() {
+                return ErrorClass::Tls;
+            }
+            source = err.source();
+        }
+
+        if proxied && e.is_connect() {
+            ErrorClass::Proxy
+        } else if e.is_timeout() {
+            ErrorClass::Timeout
+        } else if e.is_connect() {
+            ErrorClass::Connect
+        } else if e.is_body() || e.is_decode() {
+            ErrorClass::Body
+        } else {
+            ErrorClass::Other
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            ErrorClass::Timeout => "timeout",
+            ErrorClass::Connect => "connect",
+            ErrorClass::Tls => "tls",
+            ErrorClass::Proxy => "proxy",
+            ErrorClass::Body => "body",
+            ErrorClass::Other => "other",
+        }
+    }
+}
+
+impl fmt::Display for ErrorClass {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+/// Outcome of a single request.
+#[derive(Clone, Debug)]
+pub struct Record {
+    /// When the request was sent, from the start of the run.
+    pub offset: Duration,
+    pub latency: Duration,
+    pub status: Option<StatusCode>,
+    pub version: Option<Version>,
+    pub error: Option<ErrorClass>,
+    /// Index of the host the request was sent to.
+    pub target: usize,
+    /// Amount of redirects followed to get to the final response.
+    pub redirects: usize,
+    /// URL of the final response, only kept for redirected requests.
+    pub final_url: Option<String>,
+    /// Size of the response body as it came over the wire.
+    pub bytes: u64,
+    /// Size of the response body once decompressed. The same as `bytes`
+    /// for uncompressed responses.
+    pub decoded_bytes: u64,
+}
+
+/// Files the report is saved to once the run is over.
+#[derive(Clone, Debug, Default)]
+pub struct Outputs {
+    pub json: Option<PathBuf>,
+    pub html: Option<PathBuf>,
+}
+
+impl Outputs {
+    pub fn save(&self, report: &Report) -> Result<(), String> {
+        if let Some(path) = &self.json {
+            report.save(path)?;
+        }
+        if let Some(path) = &self.html {
+            report.save_html(path)?;
+        }
+        Ok(())
+    }
+}
+
+/// Counters for the requests of a run, or the ones sent to one host.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct Stats {
+    pub requests: u64,
+    /// Latencies of the requests that got a response.
+    pub latencies: Latencies,
+    pub errors: BTreeMap<ErrorClass, u64>,
+    pub statuses: BTreeMap<u16, u64>,
+    pub versions: BTreeMap<String, u64>,
+    /// Size of the response bodies as they came over the wire.
+    pub bytes: u64,
+    /// Size of the response bodies once decompressed.
+    pub decoded_bytes: u64,
+    pub redirected: u64,
+    /// Where redirected requests ended up. Past `MAX_URLS` different URLs,
+    /// the rest are counted together.
+    pub final_urls: BTreeMap<String, u64>,
+}
+
+impl Stats {
+    pub fn add(&mut self, record: &Record) {
+        self.requests += 1;
+        let status = match record.status {
+            Some(status) => status,
+            None => {
+                *self
+                    .errors
+                    .entry(record.error.unwrap_or(ErrorClass::Other))
+                    .or_default() += 1;
+                return;
+            }
+        };
+
+        self.latencies.record(record.latency);
+        *self.statuses.entry(status.as_u16()).or_default() += 1;
+        if let Some(version) = record.version {
+            *self.versions.entry(format!("{:?}", version)).or_default() += 1;
+        }
+        self.bytes += record.bytes;
+        self.decoded_bytes += record.decoded_bytes;
+        if record.redirects > 0 {
+            self.redirected += 1;
+            if let Some(url) = &record.final_url {
+                self.add_final_url(url, 1);
+            }
+        }
+    }
+
+    pub fn merge(&mut self, other: &Stats) {
+        self.requests += other.requests;
+        self.latencies.merge(&other.latencies);
+        for (&class, &count) in &other.errors {
+            *self.errors.entry(class).or_default() += count;
+        }
+        for (&status, &count) in &other.statuses {
+            *self.statuses.entry(status).or_default() += count;
+        }
+        for (version, &count) in &other.versions {
+            *self.versions.entry(version.clone()).or_default() += count;
+        }
+        self.bytes += other.bytes;
+        self.decoded_bytes += other.decoded_bytes;
+        self.redirected += other.redirected;
+        for (url, &count) in &other.final_urls {
+            self.add_final_url(url, count);
+        }
+    }
+
+    /// Requests that got a response.
+    pub fn responses(&self) -> u64 {
+        self.latencies.count()
+    }
+
+    /// Requests that failed without a response.
+    pub fn failures(&self) -> u64 {
+        self.errors.values().sum()
+    }
+
+    fn add_final_url(&mut self, url: &str, count: u64) {
+        let url = if self.final_urls.len() < MAX_URLS || self.final_urls.contains_key(url) {
+            url
+        } else {
+            OTHER_URLS
+        };
+        *self.final_urls.entry(url.to_string()).or_default() += count;
+    }
+}
+
+/// Responses and errors over the course of a run, in slices of the same
+/// length. Once the run outlasts `TIMELINE_SLICES` of them, neighbouring
+/// slices are merged into ones twice as long, so a run of any length takes
+/// the same memory.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Timeline {
+    /// How long each slice lasts.
+    pub slice: Duration,
+    pub slices: Vec<Slice>,
+}
+
+/// Outcome of the requests sent during one slice of the timeline.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct Slice {
+    pub latencies: Latencies<TIMELINE_PRECISION>,
+    pub errors: u64,
+}
+
+impl Default for Timeline {
+    fn default() -> Self {
+        Self {
+            slice: TIMELINE_SLICE,
+            slices: Vec::new(),
+        }
+    }
+}
+
+impl Timeline {
+    fn add(&mut self, record: &Record) {
+        let mut i = self.index(record.offset);
+        while i >= TIMELINE_SLICES {
+            self.coarsen();
+            i = self.index(record.offset);
+        }
+        if self.slices.len() <= i {
+            self.slices.resize_with(i + 1, Slice::default);
+        }
+        match record.status {
+            Some(_) => self.slices[i].latencies.record(record.latency),
+            None => self.slices[i].errors += 1,
+        }
+    }
+
+    fn merge(&mut self, mut other: Timeline) {
+        while self.slice < other.slice {
+            self.coarsen();
+        }
+        while other.slice < self.slice {
+            other.coarsen();
+        }
+        if self.slices.len() < other.slices.len() {
+            self.slices.resize_with(other.slices.len(), Slice::default);
+        }
+        for (slice, other) in self.slices.iter_mut().zip(other.slices) {
+            slice.latencies.merge(&other.latencies);
+            slice.errors += other.errors;
+        }
+    }
+
+    fn index(&self, offset: Duration) -> usize {
+        (offset.as_nanos() / self.slice.as_nanos().max(1)) as usize
+    }
+
+    // Merges every two neighbouring slices into one twice as long.
+    fn coarsen(&mut self) {
+        let slices = std::mem::take(&mut self.slices);
+        let mut slices = slices.into_iter();
+        while let Some(mut slice) = slices.next() {
+            if let Some(next) = slices.next() {
+                slice.latencies.merge(&next.latencies);
+                slice.errors += next.errors;
+            }
+            self.slices.push(slice);
+        }
+        self.slice *= 2;
+    }
+}
+
+/// Counts the outcome of every request sent during a run and renders the
+/// end of run summary.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Report {
+    hosts: Vec<String>,
+    total: Stats,
+    /// The same counters for each of the hosts, when there's more than one.
+    targets: Vec<Stats>,
+    #[serde(default)]
+    timeline: Timeline,
+    #[serde(skip, default = "Instant::now")]
+    started: Instant,
+    elapsed: Option<Duration>,
+    /// How hard each load generator had to work, named after the agent
+    /// that sent the load when it was spread across several.
+    usage: Vec<(Option<String>, Usage)>,
+    /// How the latency histogram is drawn, which is up to whoever renders
+    /// the report rather than part of the results.
+    #[serde(skip)]
+    scale: Scale,
+}
+
+impl Report {
+    pub fn new(hosts: Vec<String>) -> Self {
+        let targets = if hosts.len() > 1 {
+            vec![Stats::default(); hosts.len()]
+        } else {
+            Vec::new()
+        };
+        Self {
+            hosts,
+            total: Stats::default(),
+            targets,
+            timeline: Timeline::default(),
+            started: Instant::now(),
+            elapsed: None,
+            usage: Vec::new(),
+            scale: Scale::default(),
+        }
+    }
+
+    pub fn set_scale(&mut self, scale: Scale) {
+        self.scale = scale;
+    }
+
+    /// Restarts the clock throughput is measured against, as the report is
+    /// made when the client is built rather than when the load starts.
+    pub fn start(&mut self) {
+        self.started = Instant::now();
+    }
+
+    /// Marks the end of the run, so the time spent rendering the report
+    /// doesn't count towards throughput, and keeps how hard burst itself
+    /// had to work.
+    pub fn finish(&mut self, usage: Usage) {
+        self.elapsed = Some(self.started.elapsed());
+        self.usage = vec![(None, usage)];
+    }
+
+    /// Saves the report as JSON.
+    pub fn save(&self, path: &Path) -> Result<(), String> {
+        let json = serde_json::to_vec_pretty(self).map_err(|e| e.to_string())?;
+        fs::write(path, json).map_err(|e| format!("Unable to write {}: {}", path.display(), e))
+    }
+
+    /// Saves the report as a self-contained HTML page with charts.
+    pub fn save_html(&self, path: &Path) -> Result<(), String> {
+        fs::write(path, Page(self).to_string())
+            .map_err(|e| format!("Unable to write {}: {}", path.display(), e))
+    }
+
+    /// Loads a report saved with `save`.
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let json =
+            fs::read(path).map_err(|e| format!("Unable to read {}: {}", path.display(), e))?;
+        serde_json::from_slice(&json)
+            .map_err(|e| format!("{} is not a burst report: {}", path.display(), e))
+    }
+
+    pub fn hosts(&self) -> &[String] {
+        &self.hosts
+    }
+
+    /// Adds the results `agent` sent. Agents start at the same time, so
+    /// the run lasts as long as the slowest one.
+    pub fn merge(&mut self, agent: &str, report: Report) {
+        self.total.merge(&report.total);
+        if self.targets.len() < report.targets.len() {
+            self.targets
+                .resize_with(report.targets.len(), Stats::default);
+        }
+        for (stats, other) in self.targets.iter_mut().zip(&report.targets) {
+            stats.merge(other);
+        }
+        self.timeline.merge(report.timeline);
+        self.elapsed = self.elapsed.max(report.elapsed);
+        self.usage.extend(
+            report
+                .usage
+                .into_iter()
+                .map(|(_, usage)| (Some(agent.to_string()), usage)),
+        );
+    }
+
+    /// How long after the start of the run `at` was.
+    pub fn offset(&self, at: Instant) -> Duration {
+        at.saturating_duration_since(self.started)
+    }
+
+    pub fn add(&mut self, record: Record) {
+        self.total.add(&record);
+        if let Some(stats) = self.targets.get_mut(record.target) {
+            stats.add(&record);
+        }
+        self.timeline.add(&record);
+    }
+
+    /// How long the run took.
+    pub fn elapsed(&self) -> Duration {
+        self.elapsed.unwrap_or_else(|| self.started.elapsed())
+    }
+
+    /// Counters for the whole run.
+    pub fn total(&self) -> &Stats {
+        &self.total
+    }
+
+    pub fn timeline(&self) -> &Timeline {
+        &self.timeline
+    }
+}
+
+fn line(f: &mut fmt::Formatter, label: &str, value: impl fmt::Display) -> fmt::Result {
+    writeln!(f, "  {:<40}{:>20}", label, value)
+}
+
+pub fn millis(d: Duration) -> String {
+    format!("{:.3}ms", d.as_secs_f64() * 1000.0)
+}
+
+impl fmt::Display for Report {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let elapsed = self.elapsed();
+        writeln!(f, "Summary:")?;
+        line(f, "running time", format!("{:.3}s", elapsed.as_secs_f64()))?;
+        summarise(f, &self.total, elapsed)?;
+
+        if !self.total.latencies.is_empty() {
+            writeln!(f, "Latency distribution:")?;
+            write!(f, "{}", Histogram::new(&self.total.latencies, self.scale))?;
+        }
+
+        for (agent, usage) in &self.usage {
+            match agent {
+                Some(agent) => writeln!(f, "Load generator {}:", agent)?,
+                None => writeln!(f, "Load generator:")?,
+            }
+            line(f, "cpu time", format!("{:.3}s", usage.cpu.as_secs_f64()))?;
+            line(
+                f,
+                "cpu usage",
+                format!("{:.1}% of {} cores", usage.cpu_usage() * 100.0, usage.cores),
+            )?;
+            line(
+                f,
+                "max memory",
+                format!("{:.1}MB", usage.max_rss as f64 / 1_000_000.0),
+            )?;
+            line(
+                f,
+                "tokio workers busy",
+                format!(
+                    "{:.1}% of {} workers",
+                    usage.worker_busy * 100.0,
+                    usage.workers
+                ),
+            )?;
+            line(f, "average scheduling lag", millis(usage.average_lag))?;
+            line(f, "max scheduling lag", millis(usage.max_lag))?;
+
+            if let Some(reason) = usage.saturation() {
+                writeln!(
+                    f,
+                    "Warning: burst was the bottleneck, {}. The results understate what the host can handle, use fewer workers or spread the load across more machines.",
+                    reason
+                )?;
+            }
+        }
+
+        // Split the summary per host so a misbehaving replica stands out.
+        for (host, stats) in self.hosts.iter().zip(&self.targets) {
+            writeln!(f, "Target {}:", host)?;
+            summarise(f, stats, elapsed)?;
+        }
+
+        Ok(())
+    }
+}
+
+fn summarise(f: &mut fmt::Formatter, stats: &Stats, elapsed: Duration) -> fmt::Result {
+    let errors = stats.failures();
+
+    line(f, "total requests", stats.requests)?;
+    line(f, "responses", stats.responses())?;
+    line(f, "errors", errors)?;
+
+    if errors > 0 {
+        writeln!(f, "Errors:")?;
+        for (class, count) in &stats.errors {
+            line(f, &class.to_string(), count)?;
+        }
+    }
+
+    if stats.responses() > 0 {
+        line(
+            f,
+            "average request lifetime",
+            millis(stats.latencies.mean()),
+        )?;
+        line(f, "max request lifetime", millis(stats.latencies.max()))?;
+        line(f, "min request lifetime", millis(stats.latencies.min()))?;
+
+        let throughput = stats.bytes as f64 / 1_000_000.0 / elapsed.as_secs_f64();
+        line(f, "bytes received", stats.bytes)?;
+        line(f, "bytes decoded", stats.decoded_bytes)?;
+        line(f, "throughput", format!("{:.3}MB/s", throughput))?;
+        line(f, "redirected responses", stats.redirected)?;
+
+        writeln!(f, "Status codes:")?;
+        for (status, count) in &stats.statuses {
+            line(f, &status.to_string(), count)?;
+        }
+
+        writeln!(f, "Protocol versions:")?;
+        for (version, count) in &stats.versions {
+            line(f, version, count)?;
+        }
+
+        if !stats.final_urls.is_empty() {
+            writeln!(f, "Redirected to:")?;
+            for (url, count) in &stats.final_urls {
+                line(f, url, count)?;
+            }
+        }
+    }
+
+    Ok(())
+}
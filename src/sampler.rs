@@ -0,0 +1,37 @@
+use std::fs;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// Writes the bodies of the first `limit` responses to disk, so there's a
+/// sample of what the host actually returned under load.
+#[derive(Debug)]
+pub struct Sampler {
+    dir: PathBuf,
+    limit: usize,
+    taken: AtomicUsize,
+}
+
+impl Sampler {
+    pub fn new(dir: PathBuf, limit: usize) -> Self {
+        fs::create_dir_all(&dir)
+            .unwrap_or_else(|e| panic!("Unable to create {}: {}", dir.display(), e));
+
+        Self {
+            dir,
+            limit,
+            taken: AtomicUsize::new(0),
+        }
+    }
+
+    /// Claims a slot in the sample. Returns false once the sample is full.
+    pub fn take(&self) -> bool {
+        self.taken.fetch_add(1, Ordering::Relaxed) < self.limit
+    }
+
+    pub async fn save(&self, id: u64, status: u16, body: &[u8]) {
+        let path = self.dir.join(format!("{}-{}.body", id, status));
+        if let Err(e) = tokio::fs::write(&path, body).await {
+            eprintln!("Unable to save response to {}: {}", path.display(), e);
+        }
+    }
+}
@@ -0,0 +1,58 @@
+use std::fmt;
+use std::fs;
+use std::io::{self, IsTerminal};
+
+/// A password, token or key. It is never printed, so it can't end up in
+/// verbose output, reports or panics by accident.
+#[derive(Clone)]
+pub struct Secret(String);
+
+impl Secret {
+    pub fn new(secret: String) -> Self {
+        Secret(secret)
+    }
+
+    pub fn expose(&self) -> &str {
+        &self.0
+    }
+
+    pub fn from_env(var: &str) -> Result<Self, String> {
+        let secret =
+            std::env::var(var).map_err(|_| format!("Environment variable {} is not set.", var))?;
+        Ok(Secret(secret))
+    }
+
+    /// Reads a secret from a file, ignoring the trailing newline most
+    /// editors add.
+    pub fn from_file(path: &str) -> Result<Self, String> {
+        let secret =
+            fs::read_to_string(path).map_err(|e| format!("Unable to read {}: {}", path, e))?;
+        Ok(Secret(
+            secret.trim_end_matches(&['\r', '\n'][..]).to_string(),
+        ))
+    }
+
+    /// Asks for the secret on the terminal without echoing it. Returns None
+    /// when burst isn't run interactively.
+    pub fn prompt(prompt: &str) -> Option<Self> {
+        if !io::stdin().is_terminal() {
+            return None;
+        }
+
+        let secret = rpassword::prompt_password(prompt)
+            .unwrap_or_else(|e| panic!("Unable to read from the terminal: {}", e));
+        Some(Secret(secret))
+    }
+}
+
+impl fmt::Debug for Secret {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "[redacted]")
+    }
+}
+
+impl fmt::Display for Secret {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "[redacted]")
+    }
+}
@@ -0,0 +1,93 @@
+use rand::Rng;
+
+use std::fs;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+
+/// How requests are distributed when there is more than one host.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Strategy {
+    RoundRobin,
+    Random,
+    LeastInFlight,
+}
+
+#[derive(Debug)]
+pub struct Targets {
+    hosts: Vec<String>,
+    strategy: Strategy,
+    next: AtomicUsize,
+    in_flight: Vec<AtomicUsize>,
+}
+
+impl Targets {
+    pub fn new(hosts: Vec<String>, strategy: Strategy) -> Self {
+        let in_flight = hosts.iter().map(|_| AtomicUsize::new(0)).collect();
+        Self {
+            hosts,
+            strategy,
+            next: AtomicUsize::new(0),
+            in_flight,
+        }
+    }
+
+    pub fn hosts(&self) -> &[String] {
+        &self.hosts
+    }
+
+    pub fn host(&self, target: usize) -> &str {
+        &self.hosts[target]
+    }
+
+    /// Picks the host the next request goes to. The host counts as having
+    /// one more request in flight until the returned guard is dropped.
+    pub fn pick(self: &Arc<Self>) -> (usize, InFlight) {
+        let target = match self.strategy {
+            _ if self.hosts.len() == 1 => 0,
+            Strategy::RoundRobin => self.next.fetch_add(1, Ordering::Relaxed) % self.hosts.len(),
+            Strategy::Random => rand::thread_rng().gen_range(0..self.hosts.len()),
+            Strategy::LeastInFlight => {
+                // Start looking from a rotating offset so ties don't always
+                // go to the first host.
+                let start = self.next.fetch_add(1, Ordering::Relaxed);
+                (0..self.hosts.len())
+                    .map(|i| (start + i) % self.hosts.len())
+                    .min_by_key(|&i| self.in_flight[i].load(Ordering::Relaxed))
+                    .unwrap_or(0)
+            }
+        };
+
+        self.in_flight[target].fetch_add(1, Ordering::Relaxed);
+        (
+            target,
+            InFlight {
+                targets: self.clone(),
+                target,
+            },
+        )
+    }
+}
+
+pub struct InFlight {
+    targets: Arc<Targets>,
+    target: usize,
+}
+
+impl Drop for InFlight {
+    fn drop(&mut self) {
+        self.targets.in_flight[self.target].fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+/// Reads hosts from a file with one URL per line. Empty lines and lines
+/// starting with '#' are skipped.
+pub fn read_hosts_file(path: &str) -> Result<Vec<String>, String> {
+    let contents =
+        fs::read_to_string(path).map_err(|e| format!("Unable to read {}: {}", path, e))?;
+    Ok(contents
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(String::from)
+        .collect())
+}
@@ -0,0 +1,85 @@
+use reqwest::tls::Version;
+use reqwest::{Certificate, ClientBuilder, Identity};
+
+use std::fs;
+
+/// Format of the client certificate used for mutual TLS.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum CertType {
+    Pem,
+    P12,
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct TlsOptions {
+    pub ca_cert: Option<Certificate>,
+    pub identity: Option<Identity>,
+    pub insecure: bool,
+    pub min_version: Option<Version>,
+}
+
+impl TlsOptions {
+    pub fn apply(&self, builder: ClientBuilder) -> ClientBuilder {
+        let mut builder = builder;
+
+        if let Some(cert) = &self.ca_cert {
+            builder = builder.add_root_certificate(cert.clone());
+        }
+        if let Some(identity) = &self.identity {
+            builder = builder.identity(identity.clone());
+        }
+        if let Some(version) = self.min_version {
+            builder = builder.min_tls_version(version);
+        }
+        if self.insecure {
+            builder = builder
+                .danger_accept_invalid_certs(true)
+                .danger_accept_invalid_hostnames(true);
+        }
+
+        builder
+    }
+}
+
+pub fn load_ca_cert(path: &str) -> Result<Certificate, String> {
+    let pem = fs::read(path).map_err(|e| format!("Unable to read {}: {}", path, e))?;
+    Certificate::from_pem(&pem)
+        .map_err(|e| format!("{} is not a valid PEM certificate: {}", path, e))
+}
+
+/// Loads the client certificate. PEM certificates need their PKCS#8 private
+/// key, PKCS#12 archives bundle it and may be protected by a password.
+pub fn load_identity(
+    cert_type: CertType,
+    cert_path: &str,
+    key_path: Option<&str>,
+    password: &str,
+) -> Result<Identity, String> {
+    let cert = fs::read(cert_path).map_err(|e| format!("Unable to read {}: {}", cert_path, e))?;
+
+    let identity = match cert_type {
+        CertType::Pem => {
+            let key_path = key_path.ok_or_else(|| {
+                String::from("A PEM client certificate requires --key to be set.")
+            })?;
+            let key =
+                fs::read(key_path).map_err(|e| format!("Unable to read {}: {}", key_path, e))?;
+            Identity::from_pkcs8_pem(&cert, &key)
+        }
+        CertType::P12 => Identity::from_pkcs12_der(&cert, password),
+    };
+
+    identity.map_err(|e| format!("{} is not a valid client certificate: {}", cert_path, e))
+}
+
+pub fn parse_version(version: &str) -> Result<Version, String> {
+    match version {
+        "1.0" => Ok(Version::TLS_1_0),
+        "1.1" => Ok(Version::TLS_1_1),
+        "1.2" => Ok(Version::TLS_1_2),
+        _ => Err(format!(
+            "{} is not a supported TLS version. Use one of: '1.0', '1.1', or '1.2'.",
+            version
+        )),
+    }
+}
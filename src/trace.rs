@@ -0,0 +1,255 @@
+use reqwest::{Method, Url};
+use serde_json::{json, Value};
+
+use tokio::sync::mpsc::{self, error::TrySendError};
+use tokio::task::JoinHandle;
+use tokio::time::{self, Instant};
+
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crate::report::ErrorClass;
+
+/// Header the W3C trace context is sent in.
+pub const TRACEPARENT: &str = "traceparent";
+
+// Spans sent to the collector per export request.
+const BATCH: usize = 512;
+// Spans waiting to be exported. When the collector can't keep up, spans
+// past these are dropped rather than kept around for the rest of the run.
+const QUEUE: usize = 8 * BATCH;
+// How long spans are gathered into a batch before it's sent anyway.
+const FLUSH_INTERVAL: Duration = Duration::from_secs(1);
+// How long the collector has to take a batch.
+const EXPORT_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// W3C trace context for the requests burst sends, and the client spans
+/// exported for them over OTLP.
+///
+/// Every request gets its own trace, named after the ids burst already
+/// gives runs and requests: the trace id is the run id followed by the
+/// request id, and the span id is the request id. So a request seen through
+/// the probes can be looked up in the tracing backend.
+#[derive(Debug)]
+pub struct Tracer {
+    endpoint: Option<Url>,
+    run: AtomicU64,
+    queue: Mutex<Option<Queue>>,
+    dropped: AtomicU64,
+    client: reqwest::Client,
+}
+
+// Spans on their way to the task exporting them in the background.
+#[derive(Debug)]
+struct Queue {
+    spans: mpsc::Sender<Value>,
+    exporter: JoinHandle<Result<usize, String>>,
+}
+
+/// What became of the spans of a run.
+pub struct Export {
+    pub exported: usize,
+    /// Spans dropped as the collector couldn't keep up.
+    pub dropped: u64,
+}
+
+/// Outcome of a request, for its span.
+pub struct Span<'a> {
+    pub id: u64,
+    pub method: &'a Method,
+    pub url: &'a str,
+    pub started: SystemTime,
+    pub latency: Duration,
+    pub status: Option<u16>,
+    pub error: Option<ErrorClass>,
+}
+
+impl Tracer {
+    /// Exports a span for every request to the OTLP/HTTP collector at
+    /// `endpoint`, if any.
+    pub fn new(endpoint: Option<Url>) -> Self {
+        Self {
+            endpoint,
+            run: AtomicU64::new(0),
+            queue: Mutex::new(None),
+            dropped: AtomicU64::new(0),
+            client: reqwest::Client::builder()
+                .timeout(EXPORT_TIMEOUT)
+                .build()
+                .expect("Unable to build client"),
+        }
+    }
+
+    /// Marks the start of run `run`, whose id the traces start with, and
+    /// starts exporting its spans in the background.
+    pub fn start(&self, run: u64) {
+        self.run.store(run, Ordering::Relaxed);
+        self.dropped.store(0, Ordering::Relaxed);
+        let endpoint = match &self.endpoint {
+            Some(endpoint) => endpoint.clone(),
+            None => return,
+        };
+
+        let (spans, queued) = mpsc::channel(QUEUE);
+        let exporter = tokio::spawn(export(self.client.clone(), endpoint, queued));
+        *self.queue.lock().unwrap() = Some(Queue { spans, exporter });
+    }
+
+    /// The `traceparent` header to send with request `id`, sampled so the
+    /// server records its side of the request too.
+    pub fn traceparent(&self, id: u64) -> String {
+        format!("00-{}-{}-01", self.trace_id(id), span_id(id))
+    }
+
+    /// Queues the span of a request to be exported.
+    pub fn record(&self, span: Span) {
+        if self.endpoint.is_none() {
+            return;
+        }
+
+        let start = unix_nanos(span.started);
+        let mut attributes = vec![
+            attribute(
+                "http.request.method",
+                json!({ "stringValue": span.method.as_str() }),
+            ),
+            attribute("url.full", json!({ "stringValue": span.url })),
+        ];
+        if let Some(status) = span.status {
+            attributes.push(attribute(
+                "http.response.status_code",
+                json!({ "intValue": status.to_string() }),
+            ));
+        }
+        // Errors are failed requests and 5xx responses, as 4xx are the
+        // server doing its job.
+        let status = match (span.error, span.status) {
+            (Some(class), _) => {
+                attributes.push(attribute(
+                    "error.type",
+                    json!({ "stringValue": class.name() }),
+                ));
+                json!({ "code": 2, "message": class.name() })
+            }
+            (None, Some(status)) if status >= 500 => {
+                attributes.push(attribute(
+                    "error.type",
+                    json!({ "stringValue": status.to_string() }),
+                ));
+                json!({ "code": 2 })
+            }
+            _ => json!({ "code": 0 }),
+        };
+
+        let span = json!({
+            "traceId": self.trace_id(span.id),
+            "spanId": span_id(span.id),
+            "name": span.method.as_str(),
+            // SPAN_KIND_CLIENT
+            "kind": 3,
+            "startTimeUnixNano": start.to_string(),
+            "endTimeUnixNano": (start + span.latency.as_nanos()).to_string(),
+            "attributes": attributes,
+            "status": status,
+        });
+        if let Some(queue) = &*self.queue.lock().unwrap() {
+            // Once the exporter has given up on the collector there's no
+            // point counting what it misses.
+            if let Err(TrySendError::Full(_)) = queue.spans.try_send(span) {
+                self.dropped.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+    }
+
+    /// Waits for the spans of the run to be exported to the collector.
+    pub async fn finish(&self) -> Result<Export, String> {
+        let queue = self.queue.lock().unwrap().take();
+        let exported = match queue {
+            // Dropping the sender lets the exporter send what's left and stop.
+            Some(Queue { spans, exporter }) => {
+                drop(spans);
+                exporter.await.map_err(|e| e.to_string())??
+            }
+            None => 0,
+        };
+        Ok(Export {
+            exported,
+            dropped: self.dropped.load(Ordering::Relaxed),
+        })
+    }
+
+    fn trace_id(&self, id: u64) -> String {
+        let trace = (self.run.load(Ordering::Relaxed) as u128) << 64 | id as u128;
+        // An all zero id is invalid.
+        format!("{:032x}", trace.max(1))
+    }
+}
+
+// Exports the spans queued during a run in batches, in JSON over OTLP/HTTP,
+// until the queue is closed. Returns how many were exported.
+async fn export(
+    client: reqwest::Client,
+    endpoint: Url,
+    mut queued: mpsc::Receiver<Value>,
+) -> Result<usize, String> {
+    let mut exported = 0;
+    let mut batch = Vec::with_capacity(BATCH);
+    while let Some(span) = queued.recv().await {
+        batch.push(span);
+        // A slow run shouldn't send a request per span, so spans are
+        // gathered for a while before the batch goes out.
+        let deadline = Instant::now() + FLUSH_INTERVAL;
+        while batch.len() < BATCH {
+            match time::timeout_at(deadline, queued.recv()).await {
+                Ok(Some(span)) => batch.push(span),
+                _ => break,
+            }
+        }
+
+        let body = json!({
+            "resourceSpans": [{
+                "resource": {
+                    "attributes": [attribute("service.name", json!({ "stringValue": env!("CARGO_PKG_NAME") }))],
+                },
+                "scopeSpans": [{
+                    "scope": { "name": env!("CARGO_PKG_NAME"), "version": env!("CARGO_PKG_VERSION") },
+                    "spans": batch,
+                }],
+            }],
+        });
+        let res = client
+            .post(endpoint.clone())
+            .json(&body)
+            .send()
+            .await
+            .map_err(|e| e.to_string())?;
+        if !res.status().is_success() {
+            return Err(format!("{} answered with {}", endpoint, res.status()));
+        }
+        exported += batch.len();
+        batch.clear();
+    }
+    Ok(exported)
+}
+
+fn span_id(id: u64) -> String {
+    format!("{:016x}", id.max(1))
+}
+
+fn attribute(key: &str, value: Value) -> Value {
+    json!({ "key": key, "value": value })
+}
+
+fn unix_nanos(time: SystemTime) -> u128 {
+    time.duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_nanos()
+}
+
+/// Turns an OTLP/HTTP endpoint into the URL traces are sent to, the same
+/// way OpenTelemetry SDKs treat `OTEL_EXPORTER_OTLP_ENDPOINT`.
+pub fn traces_url(endpoint: &str) -> Result<Url, String> {
+    let url = format!("{}/v1/traces", endpoint.trim_end_matches('/'));
+    Url::parse(&url).map_err(|e| format!("{} is not a valid OTLP endpoint: {}", endpoint, e))
+}
@@ -0,0 +1,184 @@
+use assert_cmd::prelude::*;
+use hyper::server::conn::Http;
+use hyper::service::{make_service_fn, service_fn};
+use hyper::{Body, Request, Response, Server};
+use tokio::net::TcpListener;
+
+use std::convert::Infallible;
+use std::fs;
+use std::future::Future;
+use std::io::{BufRead, BufReader};
+use std::net::{self, SocketAddr};
+use std::process::{Child, Command, Stdio};
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+pub async fn hello(_req: Request<Body>) -> Result<Response<Body>, Infallible> {
+    Ok(Response::new(Body::from("hello")))
+}
+
+// Binds a random local port and runs `serve` on it in the background, on a
+// thread with its own runtime. Returns the address it listens on.
+pub fn spawn_server<F, Fut>(serve: F) -> SocketAddr
+where
+    F: FnOnce(net::TcpListener) -> Fut + Send + 'static,
+    Fut: Future<Output = ()>,
+{
+    let listener = net::TcpListener::bind("127.0.0.1:0").unwrap();
+    listener.set_nonblocking(true).unwrap();
+    let addr = listener.local_addr().unwrap();
+
+    thread::spawn(move || {
+        let rt = tokio::runtime::Runtime::new().unwrap();
+        rt.block_on(serve(listener));
+    });
+
+    addr
+}
+
+// Starts a cleartext HTTP/2 only (h2c) server on a random local port and
+// returns its URL.
+pub fn h2c_server() -> String {
+    let addr = spawn_server(|listener| async move {
+        let make_svc = make_service_fn(|_conn| async { Ok::<_, Infallible>(service_fn(hello)) });
+        Server::from_tcp(listener)
+            .unwrap()
+            .http2_only(true)
+            .serve(make_svc)
+            .await
+            .unwrap();
+    });
+
+    format!("http://{}", addr)
+}
+
+// Starts an HTTPS server using the certificate in tests/tls/, signed by
+// tests/tls/ca.pem, and returns its URL.
+pub fn tls_server() -> String {
+    let addr = spawn_server(|listener| async move {
+        let der = fs::read("tests/tls/server.p12").unwrap();
+        let identity = native_tls::Identity::from_pkcs12(&der, "burst").unwrap();
+        let acceptor = native_tls::TlsAcceptor::new(identity).unwrap();
+        let acceptor = tokio_native_tls::TlsAcceptor::from(acceptor);
+
+        let listener = TcpListener::from_std(listener).unwrap();
+        loop {
+            let (stream, _) = listener.accept().await.unwrap();
+            let acceptor = acceptor.clone();
+            tokio::spawn(async move {
+                if let Ok(stream) = acceptor.accept(stream).await {
+                    let _ = Http::new()
+                        .serve_connection(stream, service_fn(hello))
+                        .await;
+                }
+            });
+        }
+    });
+
+    format!("https://localhost:{}", addr.port())
+}
+
+// Starts a stand-in HTTP forward proxy that answers every proxied request
+// itself, rather than forwarding it, and returns its URL.
+pub fn proxy_server() -> String {
+    let addr = spawn_server(|listener| async move {
+        let make_svc = make_service_fn(|_conn| async {
+            Ok::<_, Infallible>(service_fn(|req: Request<Body>| async move {
+                // Requests sent to a proxy carry the absolute URL.
+                let status = if req.uri().authority().is_some() {
+                    200
+                } else {
+                    400
+                };
+                Ok::<_, Infallible>(
+                    Response::builder()
+                        .status(status)
+                        .body(Body::from("proxied"))
+                        .unwrap(),
+                )
+            }))
+        });
+        Server::from_tcp(listener)
+            .unwrap()
+            .serve(make_svc)
+            .await
+            .unwrap();
+    });
+
+    format!("http://{}", addr)
+}
+
+// Starts a stand-in OTLP/HTTP collector that keeps the JSON body of every
+// export request sent to /v1/traces, and returns its URL with the bodies.
+pub fn otlp_collector() -> (String, Arc<Mutex<Vec<serde_json::Value>>>) {
+    let exports = Arc::new(Mutex::new(Vec::new()));
+    let collected = exports.clone();
+
+    let addr = spawn_server(|listener| async move {
+        let make_svc = make_service_fn(move |_conn| {
+            let exports = exports.clone();
+            async move {
+                Ok::<_, Infallible>(service_fn(move |req: Request<Body>| {
+                    let exports = exports.clone();
+                    async move {
+                        if req.uri().path() != "/v1/traces" {
+                            let res = Response::builder().status(404).body(Body::empty());
+                            return Ok::<_, Infallible>(res.unwrap());
+                        }
+                        let body = hyper::body::to_bytes(req.into_body()).await.unwrap();
+                        exports
+                            .lock()
+                            .unwrap()
+                            .push(serde_json::from_slice(&body).unwrap());
+                        Ok::<_, Infallible>(Response::new(Body::from("{}")))
+                    }
+                }))
+            }
+        });
+        Server::from_tcp(listener)
+            .unwrap()
+            .serve(make_svc)
+            .await
+            .unwrap();
+    });
+
+    (format!("http://{}", addr), collected)
+}
+
+// A burst agent running in the background. It's killed when dropped.
+pub struct Agent {
+    child: Child,
+    pub addr: String,
+}
+
+impl Drop for Agent {
+    fn drop(&mut self) {
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
+
+// The token the agents started by `agent` take load from.
+pub const AGENT_TOKEN: &str = "agent-token";
+
+// Starts a burst agent on a random local port.
+pub fn agent() -> Agent {
+    let mut child = Command::cargo_bin("burst")
+        .unwrap()
+        .args(["agent", "--listen", "127.0.0.1:0", "--token", AGENT_TOKEN])
+        .stdout(Stdio::piped())
+        .spawn()
+        .unwrap();
+
+    let mut lines = BufReader::new(child.stdout.take().unwrap()).lines();
+    let addr = lines
+        .next()
+        .unwrap()
+        .unwrap()
+        .trim_start_matches("Listening on ")
+        .to_string();
+    // Keep reading, so the agent never blocks on a full pipe.
+    thread::spawn(move || lines.for_each(drop));
+
+    Agent { child, addr }
+}